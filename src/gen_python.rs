@@ -1,19 +1,405 @@
+use crate::python_str;
 use crate::types;
 
+pub fn is_loop(args: &getopts::Matches) -> bool {
+    ["n", "p", "a", "F"].iter().any(|x| args.opt_present(x))
+}
+
+/// Field separator given by -F, and whether it should be treated as a regex.
+///
+/// As in awk, a single space splits on runs of whitespace like -a alone, so
+/// there is no separator, and any other single character is literal.  Longer
+/// separators are regexes when they contain a metacharacter or are written
+/// as `/regex/`.
+fn field_separator(args: &getopts::Matches) -> Option<(String, bool)> {
+    let sep = args.opt_str("F").filter(|x| x != " ")?;
+    if sep.len() >= 2 && sep.starts_with('/') && sep.ends_with('/') {
+        return Some((sep[1..sep.len() - 1].to_string(), true));
+    }
+    let is_regex = sep.chars().count() > 1 && sep.chars().any(|c| "\\^$.|?*+()[]{}".contains(c));
+    Some((sep, is_regex))
+}
+
+/// Input record separator selected by -0 or -z.
+#[derive(Debug, PartialEq)]
+pub enum RecordSeparator {
+    /// Lines, as Python iterates over a file (the default).
+    Newline,
+    /// Records terminated by the given character.
+    Char(char),
+    /// Paragraphs separated by one or more blank lines (-00).
+    Paragraph,
+    /// The whole input as one record (-0400 and above).
+    Slurp,
+}
+
+pub fn record_separator(args: &getopts::Matches) -> Result<RecordSeparator, String> {
+    if args.opt_present("0") && args.opt_present("z") {
+        return Err("-0 and -z cannot be used together".to_string());
+    }
+    if args.opt_present("z") {
+        return Ok(RecordSeparator::Char('\0'));
+    }
+
+    let digits = match args.opt_present("0") {
+        true => args.opt_str("0").unwrap_or_default(),
+        false => return Ok(RecordSeparator::Newline),
+    };
+    if digits.is_empty() {
+        return Ok(RecordSeparator::Char('\0'));
+    }
+    match u32::from_str_radix(&digits, 8) {
+        Ok(0) => Ok(RecordSeparator::Paragraph),
+        Ok(x) if x >= 0o400 => Ok(RecordSeparator::Slurp),
+        Ok(x) => Ok(RecordSeparator::Char(char::from_u32(x).unwrap())),
+        Err(_) => Err(format!("invalid octal number for -0: {}", digits)),
+    }
+}
+
+/// Regex matching one record (including its terminator) for -0 and -z.
+fn record_pattern(sep: &RecordSeparator) -> Option<String> {
+    match sep {
+        RecordSeparator::Char(c) => {
+            let c = format!("\\x{:02x}", *c as u32);
+            Some(format!("[^{c}]*{c}|[^{c}]+"))
+        }
+        RecordSeparator::Paragraph => Some("(?:[^\\n]+\\n?)+\\n*".to_string()),
+        RecordSeparator::Newline | RecordSeparator::Slurp => None,
+    }
+}
+
+/// A variable defined on the command line with -v.
+#[derive(Debug, PartialEq)]
+pub struct Variable {
+    pub name: String,
+    pub value: VariableValue,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum VariableValue {
+    Str(String),
+    /// `Int` or `BigInt` atom.
+    Int(types::LispAtom),
+    Float(f64),
+    Bool(bool),
+    Json(String),
+}
+
+/// Parse a -v definition: `name=value` or `name:type=value`, where type is
+/// one of str, int, float, bool or json.
+pub fn variable(spec: &str) -> Result<Variable, String> {
+    let (decl, value) = spec
+        .split_once('=')
+        .ok_or_else(|| format!("invalid -v (expected name=value): {}", spec))?;
+    let (name, ty) = decl.split_once(':').unwrap_or((decl, "str"));
+
+    let mut chars = name.chars();
+    let is_ident = matches!(chars.next(), Some(c) if c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_');
+    if !is_ident {
+        return Err(format!("invalid variable name for -v: {}", name));
+    }
+
+    let invalid = || format!("invalid {} value for -v {}: {}", ty, name, value);
+    let value = match ty {
+        "str" => VariableValue::Str(value.to_string()),
+        "int" => VariableValue::Int(types::LispAtom::parse_int(value).ok_or_else(invalid)?),
+        "float" => VariableValue::Float(value.parse().map_err(|_| invalid())?),
+        "bool" => match value {
+            "true" | "True" | "1" => VariableValue::Bool(true),
+            "false" | "False" | "0" => VariableValue::Bool(false),
+            _ => return Err(invalid()),
+        },
+        "json" => VariableValue::Json(value.to_string()),
+        _ => return Err(format!("unknown type for -v {}: {}", name, ty)),
+    };
+
+    Ok(Variable {
+        name: name.to_string(),
+        value,
+    })
+}
+
+/// Whether the generated program reads stdin through the FIFO.
+pub fn reads_stdin(args: &getopts::Matches) -> bool {
+    !args.opt_present("i") && (args.free.is_empty() || args.free.iter().any(|x| x == "-"))
+}
+
+/// Whether pype runs the program itself (-r, or an option for the
+/// interpreter) instead of printing it.
+pub fn is_run(args: &getopts::Matches) -> bool {
+    ["r", "python", "python-arg"]
+        .iter()
+        .any(|x| args.opt_present(x))
+}
+
+/// Whether stdin reaches the generated program through a FIFO.  When pype
+/// runs the interpreter itself, it inherits pype's stdin instead.
+pub fn uses_fifo(args: &getopts::Matches) -> bool {
+    reads_stdin(args) && !is_run(args)
+}
+
+fn implied_modules(args: &getopts::Matches) -> Vec<&'static str> {
+    let mut modules = Vec::new();
+    let variables = args.opt_strs("v");
+    if variables.iter().any(|x| {
+        matches!(
+            variable(x),
+            Ok(Variable {
+                value: VariableValue::Json(_),
+                ..
+            })
+        )
+    }) {
+        modules.push("json");
+    }
+    let re_for_records = record_pattern(&record_separator(args).unwrap()).is_some();
+    let re_for_fields = matches!(field_separator(args), Some((_, true)));
+    if is_loop(args) && (re_for_records || re_for_fields) {
+        modules.push("re");
+    }
+    if args.opt_present("skip-errors") {
+        modules.extend(["builtins", "sys"]);
+    }
+    if args.opt_present("i") {
+        modules.extend(["contextlib", "os", "shutil", "tempfile"]);
+    } else if !args.free.is_empty() {
+        modules.push("fileinput");
+        if uses_fifo(args) && !modules.contains(&"sys") {
+            modules.push("sys");
+        }
+    }
+    modules
+}
+
 pub fn do_inpt(
     cur: types::LispExpRef,
-    fifo_path_str: &str,
+    fifo_path_str: Option<&str>,
+    opts: &getopts::Options,
+    args: &getopts::Matches,
+    arena: &mut types::LispArena,
+) -> types::LispExpRef {
+    if args.opt_present("i") {
+        return do_inpt_in_place(cur, opts, args, arena);
+    }
+
+    let s_with = arena.alloc(types::LispAtom::new_symbol("with").into());
+    let s_call = arena.alloc(types::LispAtom::new_symbol("call").into());
+    let s_open = arena.alloc(types::LispAtom::new_symbol("open").into());
+    let s_f = arena.alloc(types::LispAtom::new_symbol("f").into());
+    let v_stdin = match fifo_path_str {
+        Some(fifo_path_str) => arena.alloc(fifo_path_str.into()),
+        None => arena.alloc(0.into()),
+    };
+
+    if args.free.is_empty() {
+        return crate::alloc!(arena, [s_with, [s_call, s_open, v_stdin], s_f, cur]);
+    }
+
+    // With file arguments, read them in turn through fileinput.  `-` is
+    // fileinput's name for sys.stdin, which is rebound to the FIFO if any.
+    let s_attr = arena.alloc(types::LispAtom::new_symbol("attr").into());
+    let s_kw = arena.alloc(types::LispAtom::new_symbol("kw").into());
+    let s_list = arena.alloc(types::LispAtom::new_symbol("list").into());
+    let s_fileinput = arena.alloc(types::LispAtom::new_symbol("fileinput").into());
+    let s_input = arena.alloc(types::LispAtom::new_symbol("input").into());
+    let s_files = arena.alloc(types::LispAtom::new_symbol("files").into());
+
+    let mut files = crate::alloc!(arena, []);
+    for file in args.free.iter().rev() {
+        let v = arena.alloc(file.as_str().into());
+        files = crate::alloc!(arena, [v; files]);
+    }
+
+    let stmt_fileinput = crate::alloc!(
+        arena,
+        [
+            s_with,
+            [
+                s_call,
+                [s_attr, s_fileinput, s_input],
+                [s_kw, s_files, [s_list; files]]
+            ],
+            s_f,
+            cur
+        ]
+    );
+
+    match uses_fifo(args) {
+        true => {
+            let s_sys = arena.alloc(types::LispAtom::new_symbol("sys").into());
+            let s_stdin = arena.alloc(types::LispAtom::new_symbol("stdin").into());
+            crate::alloc!(
+                arena,
+                [
+                    s_with,
+                    [s_call, s_open, v_stdin],
+                    [s_attr, s_sys, s_stdin],
+                    stmt_fileinput
+                ]
+            )
+        }
+        false => stmt_fileinput,
+    }
+}
+
+/// Rewrite every file given on the command line.
+///
+/// The output of each file is written to a temporary file in the same
+/// directory, which is then renamed over the original.
+fn do_inpt_in_place(
+    cur: types::LispExpRef,
     _opts: &getopts::Options,
-    _args: &getopts::Matches,
+    args: &getopts::Matches,
     arena: &mut types::LispArena,
 ) -> types::LispExpRef {
+    let s_for = arena.alloc(types::LispAtom::new_symbol("for").into());
     let s_with = arena.alloc(types::LispAtom::new_symbol("with").into());
+    let s_progn = arena.alloc(types::LispAtom::new_symbol("progn").into());
     let s_call = arena.alloc(types::LispAtom::new_symbol("call").into());
+    let s_attr = arena.alloc(types::LispAtom::new_symbol("attr").into());
+    let s_kw = arena.alloc(types::LispAtom::new_symbol("kw").into());
+    let s_list = arena.alloc(types::LispAtom::new_symbol("list").into());
+    let s_nil = crate::alloc!(arena, []);
+    let s_assign = arena.alloc(types::LispAtom::new_symbol("assign").into());
+    let s_try = arena.alloc(types::LispAtom::new_symbol("try").into());
+    let s_except = arena.alloc(types::LispAtom::new_symbol("except").into());
+    let s_raise = arena.alloc(types::LispAtom::new_symbol("raise").into());
+    let v_false = arena.alloc(types::LispAtom::Bool(false).into());
+
     let s_open = arena.alloc(types::LispAtom::new_symbol("open").into());
     let s_f = arena.alloc(types::LispAtom::new_symbol("f").into());
-    let v_fifo_path = arena.alloc(fifo_path_str.into());
+    let s_filename = arena.alloc(types::LispAtom::new_symbol("filename").into());
+    let s_out = arena.alloc(types::LispAtom::new_symbol("_pype_out").into());
+    let s_name = arena.alloc(types::LispAtom::new_symbol("name").into());
+    let s_os = arena.alloc(types::LispAtom::new_symbol("os").into());
+    let s_path = arena.alloc(types::LispAtom::new_symbol("path").into());
+    let s_abspath = arena.alloc(types::LispAtom::new_symbol("abspath").into());
+    let s_dirname = arena.alloc(types::LispAtom::new_symbol("dirname").into());
+    let s_replace = arena.alloc(types::LispAtom::new_symbol("replace").into());
+    let s_unlink = arena.alloc(types::LispAtom::new_symbol("unlink").into());
+    let s_shutil = arena.alloc(types::LispAtom::new_symbol("shutil").into());
+    let s_copymode = arena.alloc(types::LispAtom::new_symbol("copymode").into());
+    let s_copy2 = arena.alloc(types::LispAtom::new_symbol("copy2").into());
+    let s_tempfile = arena.alloc(types::LispAtom::new_symbol("tempfile").into());
+    let s_ntf = arena.alloc(types::LispAtom::new_symbol("NamedTemporaryFile").into());
+    let s_contextlib = arena.alloc(types::LispAtom::new_symbol("contextlib").into());
+    let s_redirect = arena.alloc(types::LispAtom::new_symbol("redirect_stdout").into());
+    let s_dir = arena.alloc(types::LispAtom::new_symbol("dir").into());
+    let s_delete = arena.alloc(types::LispAtom::new_symbol("delete").into());
+    let s_join = arena.alloc(types::LispAtom::new_symbol("join").into());
+    let v_w = arena.alloc("w".into());
+    let v_empty = arena.alloc("".into());
+
+    let mut files = crate::alloc!(arena, []);
+    for file in args.free.iter().rev() {
+        let v = arena.alloc(file.as_str().into());
+        files = crate::alloc!(arena, [v; files]);
+    }
+
+    let exp_tempfile = crate::alloc!(
+        arena,
+        [
+            s_call,
+            [s_attr, s_tempfile, s_ntf],
+            v_w,
+            [
+                s_kw,
+                s_dir,
+                [
+                    s_call,
+                    [s_attr, [s_attr, s_os, s_path], s_dirname],
+                    [
+                        s_call,
+                        [s_attr, [s_attr, s_os, s_path], s_abspath],
+                        s_filename
+                    ]
+                ]
+            ],
+            [s_kw, s_delete, v_false]
+        ]
+    );
+    let stmt_edit = crate::alloc!(
+        arena,
+        [
+            s_with,
+            s_out,
+            s_nil,
+            [
+                s_with,
+                [s_call, [s_attr, s_contextlib, s_redirect], s_out],
+                s_nil,
+                cur
+            ]
+        ]
+    );
+    let stmt_copymode = crate::alloc!(
+        arena,
+        [
+            s_call,
+            [s_attr, s_shutil, s_copymode],
+            s_filename,
+            [s_attr, s_out, s_name]
+        ]
+    );
+    let stmt_replace = crate::alloc!(
+        arena,
+        [
+            s_call,
+            [s_attr, s_os, s_replace],
+            [s_attr, s_out, s_name],
+            s_filename
+        ]
+    );
+
+    let stmts = match args.opt_str("i").filter(|x| !x.is_empty()) {
+        Some(suffix) => {
+            let v_suffix = arena.alloc(suffix.into());
+            let stmt_backup = crate::alloc!(
+                arena,
+                [
+                    s_call,
+                    [s_attr, s_shutil, s_copy2],
+                    s_filename,
+                    [
+                        s_call,
+                        [s_attr, v_empty, s_join],
+                        [s_list, s_filename, v_suffix]
+                    ]
+                ]
+            );
+            crate::alloc!(arena, [stmt_edit, stmt_copymode, stmt_backup, stmt_replace])
+        }
+        None => crate::alloc!(arena, [stmt_edit, stmt_copymode, stmt_replace]),
+    };
 
-    crate::alloc!(arena, [s_with, [s_call, s_open, v_fifo_path], s_f, cur])
+    // The temporary file is removed again if anything fails before it
+    // replaces the original.
+    let stmt_cleanup = crate::alloc!(
+        arena,
+        [
+            s_except,
+            s_nil,
+            s_nil,
+            [s_call, [s_attr, s_os, s_unlink], [s_attr, s_out, s_name]],
+            [s_raise]
+        ]
+    );
+    let body = crate::alloc!(
+        arena,
+        [
+            s_with,
+            [s_call, s_open, s_filename],
+            s_f,
+            [
+                s_progn,
+                [s_assign, s_out, exp_tempfile],
+                [s_try, [s_progn; stmts], stmt_cleanup]
+            ]
+        ]
+    );
+
+    crate::alloc!(arena, [s_for, s_filename, [s_list; files], body])
 }
 
 pub fn do_e(
@@ -32,94 +418,448 @@ pub fn do_e(
     crate::alloc!(arena, [s_progn; cur])
 }
 
+/// Read the program from -L and --lisp-file instead of -e.
+pub fn do_lisp(
+    _opts: &getopts::Options,
+    args: &getopts::Matches,
+    arena: &mut types::LispArena,
+) -> Result<types::LispExpRef, String> {
+    let mut sources = args
+        .opt_strs("L")
+        .into_iter()
+        .map(|x| ("-L".to_string(), x))
+        .collect::<Vec<_>>();
+    for path in args.opt_strs("lisp-file") {
+        let src = std::fs::read_to_string(&path).map_err(|err| format!("{}: {}", path, err))?;
+        sources.push((path, src));
+    }
+
+    let mut exps = Vec::new();
+    for (name, src) in sources {
+        let read =
+            crate::reader::read_all(&src, arena).map_err(|err| format!("{}:{}", name, err))?;
+        exps.extend(read);
+    }
+
+    let s_progn = arena.alloc(types::LispAtom::new_symbol("progn").into());
+
+    let mut cur = crate::alloc!(arena, []);
+    for exp in exps.into_iter().rev() {
+        cur = crate::alloc!(arena, [exp; cur]);
+    }
+    Ok(crate::alloc!(arena, [s_progn; cur]))
+}
+
+pub fn do_skip_errors(
+    cur: types::LispExpRef,
+    _opts: &getopts::Options,
+    args: &getopts::Matches,
+    arena: &mut types::LispArena,
+) -> types::LispExpRef {
+    if !args.opt_present("skip-errors") {
+        return cur;
+    }
+
+    let s_try = arena.alloc(types::LispAtom::new_symbol("try").into());
+    let s_except = arena.alloc(types::LispAtom::new_symbol("except").into());
+    let s_exception = arena.alloc(types::LispAtom::new_symbol("Exception").into());
+    let s_err = arena.alloc(types::LispAtom::new_symbol("_pype_err").into());
+    let s_call = arena.alloc(types::LispAtom::new_symbol("call").into());
+    let s_attr = arena.alloc(types::LispAtom::new_symbol("attr").into());
+    let s_kw = arena.alloc(types::LispAtom::new_symbol("kw").into());
+    let s_builtins = arena.alloc(types::LispAtom::new_symbol("builtins").into());
+    let s_print = arena.alloc(types::LispAtom::new_symbol("print").into());
+    let s_format = arena.alloc(types::LispAtom::new_symbol("format").into());
+    let s_line = arena.alloc(types::LispAtom::new_symbol("line").into());
+    let s_file = arena.alloc(types::LispAtom::new_symbol("file").into());
+    let s_sys = arena.alloc(types::LispAtom::new_symbol("sys").into());
+    let s_stderr = arena.alloc(types::LispAtom::new_symbol("stderr").into());
+    let v_message = arena.alloc("pype: skipping {!r}: {!r}".into());
+
+    // try:
+    //     BODY
+    // except Exception as _pype_err:
+    //     builtins.print("pype: skipping {!r}: {!r}".format(line, _pype_err), file=sys.stderr)
+    let exp_message = crate::alloc!(
+        arena,
+        [s_call, [s_attr, v_message, s_format], s_line, s_err]
+    );
+    let exp_report = crate::alloc!(
+        arena,
+        [
+            s_call,
+            [s_attr, s_builtins, s_print],
+            exp_message,
+            [s_kw, s_file, [s_attr, s_sys, s_stderr]]
+        ]
+    );
+    crate::alloc!(
+        arena,
+        [s_try, cur, [s_except, s_exception, s_err, exp_report]]
+    )
+}
+
 pub fn do_n(
     cur: types::LispExpRef,
     _opts: &getopts::Options,
     args: &getopts::Matches,
     arena: &mut types::LispArena,
 ) -> types::LispExpRef {
-    match args.opt_present("n") {
+    if !is_loop(args) {
+        return cur;
+    }
+
+    let s_for = arena.alloc(types::LispAtom::new_symbol("for").into());
+    let s_line = arena.alloc(types::LispAtom::new_symbol("line").into());
+    let s_f = arena.alloc(types::LispAtom::new_symbol("f").into());
+
+    let sep = record_separator(args).unwrap();
+    if sep == RecordSeparator::Newline {
+        return crate::alloc!(arena, [s_for, s_line, s_f, cur]);
+    }
+
+    let s_call = arena.alloc(types::LispAtom::new_symbol("call").into());
+    let s_attr = arena.alloc(types::LispAtom::new_symbol("attr").into());
+    let s_list = arena.alloc(types::LispAtom::new_symbol("list").into());
+    let v_empty = arena.alloc("".into());
+    let Some(pattern) = record_pattern(&sep) else {
+        // Slurp: the whole input as one record.  With file arguments f is a
+        // fileinput.FileInput, which has no read().
+        let exp_text = match args.free.is_empty() || args.opt_present("i") {
+            true => {
+                let s_read = arena.alloc(types::LispAtom::new_symbol("read").into());
+                crate::alloc!(arena, [s_call, [s_attr, s_f, s_read]])
+            }
+            false => {
+                let s_join = arena.alloc(types::LispAtom::new_symbol("join").into());
+                crate::alloc!(arena, [s_call, [s_attr, v_empty, s_join], s_f])
+            }
+        };
+        return crate::alloc!(arena, [s_for, s_line, [s_list, exp_text], cur]);
+    };
+
+    // Other separators split the input as it is read.  Of the records found
+    // so far, the last one may continue in the next chunk, so it is kept
+    // back until then.  Chunks are lines when f is a fileinput.FileInput.
+    let s_progn = arena.alloc(types::LispAtom::new_symbol("progn").into());
+    let s_def = arena.alloc(types::LispAtom::new_symbol("def").into());
+    let s_assign = arena.alloc(types::LispAtom::new_symbol("assign").into());
+    let s_ifexp = arena.alloc(types::LispAtom::new_symbol("ifexp").into());
+    let s_when = arena.alloc(types::LispAtom::new_symbol("when").into());
+    let s_yield = arena.alloc(types::LispAtom::new_symbol("yield").into());
+    let s_plus = arena.alloc(types::LispAtom::new_symbol("+").into());
+    let s_re = arena.alloc(types::LispAtom::new_symbol("re").into());
+    let s_findall = arena.alloc(types::LispAtom::new_symbol("findall").into());
+    let s_pop = arena.alloc(types::LispAtom::new_symbol("pop").into());
+    let s_records = arena.alloc(types::LispAtom::new_symbol("_pype_records").into());
+    let s_chunks = arena.alloc(types::LispAtom::new_symbol("chunks").into());
+    let s_chunk = arena.alloc(types::LispAtom::new_symbol("chunk").into());
+    let s_buf = arena.alloc(types::LispAtom::new_symbol("buf").into());
+    let s_found = arena.alloc(types::LispAtom::new_symbol("found").into());
+    let s_record = arena.alloc(types::LispAtom::new_symbol("record").into());
+    let v_pattern = arena.alloc(pattern.into());
+
+    let exp_chunks = match args.free.is_empty() || args.opt_present("i") {
         true => {
-            let s_for = arena.alloc(types::LispAtom::new_symbol("for").into());
-            let s_line = arena.alloc(types::LispAtom::new_symbol("line").into());
-            let s_f = arena.alloc(types::LispAtom::new_symbol("f").into());
-            crate::alloc!(arena, [s_for, s_line, s_f, cur])
+            let s_iter = arena.alloc(types::LispAtom::new_symbol("iter").into());
+            let s_lambda = arena.alloc(types::LispAtom::new_symbol("lambda").into());
+            let s_read = arena.alloc(types::LispAtom::new_symbol("read").into());
+            let v_size = arena.alloc(65536.into());
+            let nil = crate::alloc!(arena, []);
+            crate::alloc!(
+                arena,
+                [
+                    s_call,
+                    s_iter,
+                    [s_lambda, nil, [s_call, [s_attr, s_f, s_read], v_size]],
+                    v_empty
+                ]
+            )
         }
-        false => cur,
+        false => s_f,
+    };
+
+    let stmt_def = crate::alloc!(
+        arena,
+        [
+            s_def,
+            s_records,
+            [s_chunks],
+            [s_assign, s_buf, v_empty],
+            [
+                s_for,
+                s_chunk,
+                s_chunks,
+                [
+                    s_progn,
+                    [
+                        s_assign,
+                        s_found,
+                        [
+                            s_call,
+                            [s_attr, s_re, s_findall],
+                            v_pattern,
+                            [s_plus, s_buf, s_chunk]
+                        ]
+                    ],
+                    [
+                        s_assign,
+                        s_buf,
+                        [
+                            s_ifexp,
+                            s_found,
+                            [s_call, [s_attr, s_found, s_pop]],
+                            v_empty
+                        ]
+                    ],
+                    [s_for, s_record, s_found, [s_yield, s_record]]
+                ]
+            ],
+            [s_when, s_buf, [s_yield, s_buf]]
+        ]
+    );
+    crate::alloc!(
+        arena,
+        [
+            s_progn,
+            stmt_def,
+            [s_for, s_line, [s_call, s_records, exp_chunks], cur]
+        ]
+    )
+}
+
+pub fn do_begin_end(
+    cur: types::LispExpRef,
+    _opts: &getopts::Options,
+    args: &getopts::Matches,
+    arena: &mut types::LispArena,
+) -> types::LispExpRef {
+    let begins = args.opt_strs("B");
+    let ends = args.opt_strs("E");
+    if begins.is_empty() && ends.is_empty() {
+        return cur;
+    }
+
+    let s_progn = arena.alloc(types::LispAtom::new_symbol("progn").into());
+
+    let mut tail = crate::alloc!(arena, []);
+    for cmd in ends.iter().rev() {
+        let v = arena.alloc(types::LispAtom::new_raw_text(cmd).into());
+        tail = crate::alloc!(arena, [v; tail]);
+    }
+    tail = crate::alloc!(arena, [cur; tail]);
+    for cmd in begins.iter().rev() {
+        let v = arena.alloc(types::LispAtom::new_raw_text(cmd).into());
+        tail = crate::alloc!(arena, [v; tail]);
     }
+    crate::alloc!(arena, [s_progn; tail])
 }
 
-pub fn do_l(
+pub fn do_v(
     cur: types::LispExpRef,
     _opts: &getopts::Options,
     args: &getopts::Matches,
     arena: &mut types::LispArena,
 ) -> types::LispExpRef {
-    if args.opt_present("l") && args.opt_present("n") {
-        let s_progn = arena.alloc(types::LispAtom::new_symbol("progn").into());
-        let s_assign = arena.alloc(types::LispAtom::new_symbol("assign").into());
-        let s_line = arena.alloc(types::LispAtom::new_symbol("line").into());
-        let s_call = arena.alloc(types::LispAtom::new_symbol("call").into());
-        let s_attr = arena.alloc(types::LispAtom::new_symbol("attr").into());
-        let s_rstrip = arena.alloc(types::LispAtom::new_symbol("rstrip").into());
-        crate::alloc!(
-            arena,
-            [
-                s_progn,
-                [s_assign, s_line, [s_call, [s_attr, s_line, s_rstrip]]],
-                cur
-            ]
-        )
-    } else {
-        cur
+    let variables = args.opt_strs("v");
+    if variables.is_empty() {
+        return cur;
     }
+
+    let s_progn = arena.alloc(types::LispAtom::new_symbol("progn").into());
+    let s_assign = arena.alloc(types::LispAtom::new_symbol("assign").into());
+
+    let mut cur = crate::alloc!(arena, [cur]);
+    for spec in variables.iter().rev() {
+        let var = variable(spec).unwrap();
+        let s_name = arena.alloc(types::LispAtom::new_symbol(var.name).into());
+        let v_value = match var.value {
+            VariableValue::Str(x) => arena.alloc(x.into()),
+            VariableValue::Int(x) => arena.alloc(x.into()),
+            VariableValue::Float(x) => arena.alloc(x.into()),
+            VariableValue::Bool(x) => arena.alloc(x.into()),
+            VariableValue::Json(x) => {
+                let s_call = arena.alloc(types::LispAtom::new_symbol("call").into());
+                let s_attr = arena.alloc(types::LispAtom::new_symbol("attr").into());
+                let s_json = arena.alloc(types::LispAtom::new_symbol("json").into());
+                let s_loads = arena.alloc(types::LispAtom::new_symbol("loads").into());
+                let v = arena.alloc(x.into());
+                crate::alloc!(arena, [s_call, [s_attr, s_json, s_loads], v])
+            }
+        };
+        cur = crate::alloc!(arena, [[s_assign, s_name, v_value]; cur]);
+    }
+    crate::alloc!(arena, [s_progn; cur])
 }
 
-pub fn do_l_post(
+pub fn do_a(
     cur: types::LispExpRef,
     _opts: &getopts::Options,
     args: &getopts::Matches,
     arena: &mut types::LispArena,
 ) -> types::LispExpRef {
-    match args.opt_present("l") {
-        true => cur,
-        false => {
-            let s_progn = arena.alloc(types::LispAtom::new_symbol("progn").into());
-            let s_import = arena.alloc(types::LispAtom::new_symbol("import").into());
-            let s_assign = arena.alloc(types::LispAtom::new_symbol("assign").into());
-            let s_star = arena.alloc(types::LispAtom::new_symbol("*").into());
-            let s_dstar = arena.alloc(types::LispAtom::new_symbol("**").into());
-            let s_kw = arena.alloc(types::LispAtom::new_symbol("kw").into());
-            let s_attr = arena.alloc(types::LispAtom::new_symbol("attr").into());
-            let s_call = arena.alloc(types::LispAtom::new_symbol("call").into());
+    if !(args.opt_present("a") || args.opt_present("F")) {
+        return cur;
+    }
 
-            let s_builtins = arena.alloc(types::LispAtom::new_symbol("builtins").into());
-            let s_print = arena.alloc(types::LispAtom::new_symbol("print").into());
-            let s_lambda = arena.alloc(types::LispAtom::new_symbol("lambda").into());
-            let s_args = arena.alloc(types::LispAtom::new_symbol("args").into());
-            let s_kwargs = arena.alloc(types::LispAtom::new_symbol("kwargs").into());
-            let s_end = arena.alloc(types::LispAtom::new_symbol("end").into());
-            let v_empty = arena.alloc("".into());
+    let s_progn = arena.alloc(types::LispAtom::new_symbol("progn").into());
+    let s_assign = arena.alloc(types::LispAtom::new_symbol("assign").into());
+    let s_call = arena.alloc(types::LispAtom::new_symbol("call").into());
+    let s_attr = arena.alloc(types::LispAtom::new_symbol("attr").into());
+    let s_line = arena.alloc(types::LispAtom::new_symbol("line").into());
+    let s_fields = arena.alloc(types::LispAtom::new_symbol("F").into());
+    let s_split = arena.alloc(types::LispAtom::new_symbol("split").into());
+
+    let exp_split = match field_separator(args) {
+        None => crate::alloc!(arena, [s_call, [s_attr, s_line, s_split]]),
+        Some((sep, false)) => {
+            let v_sep = arena.alloc(sep.into());
+            crate::alloc!(arena, [s_call, [s_attr, s_line, s_split], v_sep])
+        }
+        Some((sep, true)) => {
+            let s_re = arena.alloc(types::LispAtom::new_symbol("re").into());
+            let v_sep = arena.alloc(sep.into());
+            crate::alloc!(arena, [s_call, [s_attr, s_re, s_split], v_sep, s_line])
+        }
+    };
+
+    crate::alloc!(arena, [s_progn, [s_assign, s_fields, exp_split], cur])
+}
+
+pub fn do_l(
+    cur: types::LispExpRef,
+    _opts: &getopts::Options,
+    args: &getopts::Matches,
+    arena: &mut types::LispArena,
+) -> types::LispExpRef {
+    if !(args.opt_present("l") && is_loop(args)) {
+        return cur;
+    }
 
-            let stmt_import = crate::alloc!(arena, [s_import, s_builtins]);
-            let exp_print = crate::alloc!(
+    let s_progn = arena.alloc(types::LispAtom::new_symbol("progn").into());
+    let s_assign = arena.alloc(types::LispAtom::new_symbol("assign").into());
+    let s_line = arena.alloc(types::LispAtom::new_symbol("line").into());
+    let s_call = arena.alloc(types::LispAtom::new_symbol("call").into());
+    let s_attr = arena.alloc(types::LispAtom::new_symbol("attr").into());
+    let s_rstrip = arena.alloc(types::LispAtom::new_symbol("rstrip").into());
+
+    let exp_strip = match record_separator(args).unwrap() {
+        // A line ends in at most one newline, and other trailing whitespace
+        // is data.
+        RecordSeparator::Newline => {
+            let v_sep = arena.alloc("\n".into());
+            crate::alloc!(arena, [s_call, [s_attr, s_line, s_rstrip], v_sep])
+        }
+        // Only the record's own separator, which it may lack at the end.
+        RecordSeparator::Char(c) => {
+            let s_ifexp = arena.alloc(types::LispAtom::new_symbol("ifexp").into());
+            let s_endswith = arena.alloc(types::LispAtom::new_symbol("endswith").into());
+            let s_subscript = arena.alloc(types::LispAtom::new_symbol("subscript").into());
+            let s_slice = arena.alloc(types::LispAtom::new_symbol("slice").into());
+            let v_sep = arena.alloc(c.to_string().into());
+            let v_last = arena.alloc((-1).into());
+            let nil = crate::alloc!(arena, []);
+            crate::alloc!(
                 arena,
                 [
-                    s_call,
-                    [s_attr, s_builtins, s_print],
-                    [s_star, s_args],
-                    [s_dstar, s_kwargs],
-                    [s_kw, s_end, v_empty]
+                    s_ifexp,
+                    [s_call, [s_attr, s_line, s_endswith], v_sep],
+                    [s_subscript, s_line, [s_slice, nil, v_last]],
+                    s_line
                 ]
-            );
-            let exp_lambda = crate::alloc!(
-                arena,
-                [s_lambda, [[s_star, s_args], [s_dstar, s_kwargs]], exp_print]
-            );
-            let stmt_assign = crate::alloc!(arena, [s_assign, s_print, exp_lambda]);
-            crate::alloc!(arena, [s_progn, stmt_import, stmt_assign, cur])
+            )
+        }
+        // All the blank lines after a paragraph, as Perl's chomp does with
+        // -00.
+        RecordSeparator::Paragraph => {
+            let v_sep = arena.alloc("\n".into());
+            crate::alloc!(arena, [s_call, [s_attr, s_line, s_rstrip], v_sep])
+        }
+        RecordSeparator::Slurp => return cur,
+    };
+
+    crate::alloc!(arena, [s_progn, [s_assign, s_line, exp_strip], cur])
+}
+
+pub fn do_p(
+    cur: types::LispExpRef,
+    _opts: &getopts::Options,
+    args: &getopts::Matches,
+    arena: &mut types::LispArena,
+) -> types::LispExpRef {
+    // The line is printed in a finally clause so that, as with Perl's next,
+    // a continue still prints it.
+    match args.opt_present("p") {
+        true => {
+            let s_try = arena.alloc(types::LispAtom::new_symbol("try").into());
+            let s_finally = arena.alloc(types::LispAtom::new_symbol("finally").into());
+            let s_call = arena.alloc(types::LispAtom::new_symbol("call").into());
+            let s_print = arena.alloc(types::LispAtom::new_symbol("print").into());
+            let s_line = arena.alloc(types::LispAtom::new_symbol("line").into());
+            crate::alloc!(arena, [s_try, cur, [s_finally, [s_call, s_print, s_line]]])
         }
+        false => cur,
+    }
+}
+
+/// Override print with the output record separator (-O, or "" without -l,
+/// since lines keep their newline) and the output field separator (--ofs).
+pub fn do_l_post(
+    cur: types::LispExpRef,
+    _opts: &getopts::Options,
+    args: &getopts::Matches,
+    arena: &mut types::LispArena,
+) -> types::LispExpRef {
+    let ors = match (args.opt_str("O"), args.opt_present("l")) {
+        (Some(ors), _) => Some(python_str::unescape(&ors)),
+        (None, false) => Some("".to_string()),
+        (None, true) => None,
+    };
+    let ofs = args.opt_str("ofs").map(|x| python_str::unescape(&x));
+
+    let mut overrides = Vec::new();
+    if let Some(ors) = ors {
+        overrides.push(("end", ors));
     }
+    if let Some(ofs) = ofs {
+        overrides.push(("sep", ofs));
+    }
+    if overrides.is_empty() {
+        return cur;
+    }
+
+    let s_progn = arena.alloc(types::LispAtom::new_symbol("progn").into());
+    let s_import = arena.alloc(types::LispAtom::new_symbol("import").into());
+    let s_star = arena.alloc(types::LispAtom::new_symbol("*").into());
+    let s_dstar = arena.alloc(types::LispAtom::new_symbol("**").into());
+    let s_kw = arena.alloc(types::LispAtom::new_symbol("kw").into());
+    let s_attr = arena.alloc(types::LispAtom::new_symbol("attr").into());
+    let s_call = arena.alloc(types::LispAtom::new_symbol("call").into());
+
+    let s_builtins = arena.alloc(types::LispAtom::new_symbol("builtins").into());
+    let s_print = arena.alloc(types::LispAtom::new_symbol("print").into());
+    let s_def = arena.alloc(types::LispAtom::new_symbol("def").into());
+    let s_return = arena.alloc(types::LispAtom::new_symbol("return").into());
+    let s_args = arena.alloc(types::LispAtom::new_symbol("args").into());
+    let s_kwargs = arena.alloc(types::LispAtom::new_symbol("kwargs").into());
+
+    // def print(*args, end=ORS, sep=OFS, **kwargs):
+    //     return builtins.print(*args, end=end, sep=sep, **kwargs)
+    let mut params = crate::alloc!(arena, [[s_dstar, s_kwargs]]);
+    let mut fn_args = crate::alloc!(arena, [[s_dstar, s_kwargs]]);
+    for (name, value) in overrides.into_iter().rev() {
+        let s_name = arena.alloc(types::LispAtom::new_symbol(name).into());
+        let v_value = arena.alloc(value.into());
+        params = crate::alloc!(arena, [[s_kw, s_name, v_value]; params]);
+        fn_args = crate::alloc!(arena, [[s_kw, s_name, s_name]; fn_args]);
+    }
+    let params = crate::alloc!(arena, [[s_star, s_args]; params]);
+    let fn_args = crate::alloc!(arena, [[s_star, s_args]; fn_args]);
+
+    let stmt_import = crate::alloc!(arena, [s_import, s_builtins]);
+    let exp_print = crate::alloc!(arena, [s_call, [s_attr, s_builtins, s_print]; fn_args]);
+    let stmt_def = crate::alloc!(arena, [s_def, s_print, params, [s_return, exp_print]]);
+    crate::alloc!(arena, [s_progn, stmt_import, stmt_def, cur])
 }
 
 pub fn do_m(
@@ -128,7 +868,11 @@ pub fn do_m(
     args: &getopts::Matches,
     arena: &mut types::LispArena,
 ) -> types::LispExpRef {
-    let modules = args.opt_strs("m");
+    let mut modules = implied_modules(args)
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+    modules.extend(args.opt_strs("m"));
     if modules.is_empty() {
         return cur;
     }
@@ -143,3 +887,288 @@ pub fn do_m(
     }
     crate::alloc!(arena, [s_progn; cur])
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(argv: &[&str]) -> getopts::Matches {
+        let mut opts = getopts::Options::new();
+        opts.optflagopt("0", "record-separator", "", "OCTAL");
+        opts.optflag("z", "null-data", "");
+        opts.optopt("F", "", "", "sep");
+        for flag in ["n", "p", "a", "l", "r"] {
+            opts.optflag(flag, "", "");
+        }
+        opts.optflag("", "skip-errors", "");
+        opts.optflagopt("i", "in-place", "", "SUFFIX");
+        for opt in ["e", "B", "E", "v", "m", "O", "L"] {
+            opts.optmulti(opt, "", "", "");
+        }
+        for opt in ["python", "python-arg", "ofs", "lisp-file"] {
+            opts.optmulti("", opt, "", "");
+        }
+        opts.parse(argv).unwrap()
+    }
+
+    type Step = fn(
+        types::LispExpRef,
+        &getopts::Options,
+        &getopts::Matches,
+        &mut types::LispArena,
+    ) -> types::LispExpRef;
+
+    /// Python generated by applying `step` to the raw code `BODY`.
+    fn gen_step(step: Step, argv: &[&str]) -> String {
+        let mut arena = types::LispArena::default();
+        let body = arena.alloc(types::LispAtom::new_raw_text("BODY").into());
+        let e = step(body, &getopts::Options::new(), &parse(argv), &mut arena);
+        crate::generator::gen(&e).unwrap()
+    }
+
+    fn gen_inpt(argv: &[&str], fifo: Option<&str>) -> String {
+        let mut arena = types::LispArena::default();
+        let body = arena.alloc(types::LispAtom::new_raw_text("BODY").into());
+        let e = do_inpt(
+            body,
+            fifo,
+            &getopts::Options::new(),
+            &parse(argv),
+            &mut arena,
+        );
+        crate::generator::gen(&e).unwrap()
+    }
+
+    #[test]
+    fn test_do_inpt() {
+        assert_eq!(
+            gen_inpt(&[], Some("/tmp/x.fifo")),
+            "with open(\"/tmp/x.fifo\") as f:\n    BODY"
+        );
+        assert_eq!(gen_inpt(&["-r"], None), "with open(0) as f:\n    BODY");
+        assert_eq!(
+            gen_inpt(&["a", "b"], None),
+            "with fileinput.input(files=[\"a\", \"b\"]) as f:\n    BODY"
+        );
+
+        // `-` reads stdin, which is the FIFO unless python runs under pype.
+        let expect = "\
+with open(\"/tmp/x.fifo\") as sys.stdin:
+    with fileinput.input(files=[\"a\", \"-\"]) as f:
+        BODY";
+        assert_eq!(gen_inpt(&["a", "-"], Some("/tmp/x.fifo")), expect);
+        assert_eq!(
+            gen_inpt(&["-r", "a", "-"], None),
+            "with fileinput.input(files=[\"a\", \"-\"]) as f:\n    BODY"
+        );
+    }
+
+    #[test]
+    fn test_uses_fifo() {
+        assert!(uses_fifo(&parse(&["-n"])));
+        assert!(uses_fifo(&parse(&["-n", "a", "-"])));
+        assert!(!uses_fifo(&parse(&["-n", "--in-place", "a"])));
+        assert!(!uses_fifo(&parse(&["-n", "a"])));
+        for run in [
+            &["-r"][..],
+            &["--python", "python3"],
+            &["--python-arg", "-O"],
+        ] {
+            assert!(is_run(&parse(run)));
+            assert!(!uses_fifo(&parse(&[run, &["-n"]].concat())));
+        }
+    }
+
+    #[test]
+    fn test_do_inpt_in_place() {
+        let expect = "\
+for filename in [\"a\", \"b\"]:
+    with open(filename) as f:
+        _pype_out = tempfile.NamedTemporaryFile(\"w\", dir=os.path.dirname(os.path.abspath(filename)), delete=False)
+        try:
+            with _pype_out:
+                with contextlib.redirect_stdout(_pype_out):
+                    BODY
+            shutil.copymode(filename, _pype_out.name)
+            os.replace(_pype_out.name, filename)
+        except:
+            os.unlink(_pype_out.name)
+            raise";
+        assert_eq!(gen_inpt(&["--in-place", "a", "b"], None), expect);
+
+        let backup = "\
+            shutil.copymode(filename, _pype_out.name)
+            shutil.copy2(filename, \"\".join([filename, \".bak\"]))
+            os.replace(_pype_out.name, filename)";
+        assert!(gen_inpt(&["-i.bak", "a", "b"], None).contains(backup));
+    }
+
+    #[test]
+    fn test_do_begin_end() {
+        assert_eq!(gen_step(do_begin_end, &[]), "BODY");
+        assert_eq!(
+            gen_step(
+                do_begin_end,
+                &["-B", "a = 1", "-B", "b = 2", "-E", "print(a)"]
+            ),
+            "a = 1\nb = 2\nBODY\nprint(a)"
+        );
+    }
+
+    #[test]
+    fn test_do_l() {
+        assert_eq!(gen_step(do_l, &["-l"]), "BODY");
+        assert_eq!(
+            gen_step(do_l, &["-nl"]),
+            "line = line.rstrip(\"\\n\")\nBODY"
+        );
+        assert_eq!(
+            gen_step(do_l, &["-nl", "-z"]),
+            "line = line[:-1] if line.endswith(\"\\x00\") else line\nBODY"
+        );
+        assert_eq!(
+            gen_step(do_l, &["-nl", "-00"]),
+            "line = line.rstrip(\"\\n\")\nBODY"
+        );
+        assert_eq!(gen_step(do_l, &["-nl", "-0777"]), "BODY");
+    }
+
+    #[test]
+    fn test_do_p() {
+        assert_eq!(gen_step(do_p, &[]), "BODY");
+        assert_eq!(
+            gen_step(do_p, &["-p"]),
+            "try:\n    BODY\nfinally:\n    print(line)"
+        );
+    }
+
+    #[test]
+    fn test_do_lisp() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), "(call print x)\n(pass)\n").unwrap();
+        let path = file.path().to_str().unwrap();
+
+        let gen_lisp = |argv: &[&str]| {
+            let mut arena = types::LispArena::default();
+            let e = do_lisp(&getopts::Options::new(), &parse(argv), &mut arena)?;
+            Ok::<_, String>(crate::generator::gen(&e).unwrap())
+        };
+        assert_eq!(
+            gen_lisp(&["-L", "(assign x 1)", "--lisp-file", path]),
+            Ok("x = 1\nprint(x)\npass".to_string())
+        );
+        assert_eq!(
+            gen_lisp(&["-L", "(call print"]),
+            Err("-L:1:12: unexpected end of input".to_string())
+        );
+        let err = gen_lisp(&["--lisp-file", "/nonexistent.lisp"]).unwrap_err();
+        assert!(err.starts_with("/nonexistent.lisp: "));
+    }
+
+    #[test]
+    fn test_do_l_post() {
+        assert_eq!(gen_step(do_l_post, &["-l"]), "BODY");
+        let expect = "\
+import builtins
+def print(*args, end=\"\", **kwargs):
+    return builtins.print(*args, end=end, **kwargs)
+BODY";
+        assert_eq!(gen_step(do_l_post, &[]), expect);
+
+        let expect = "\
+import builtins
+def print(*args, end=\"\\t\", sep=\",\", **kwargs):
+    return builtins.print(*args, end=end, sep=sep, **kwargs)
+BODY";
+        let argv = ["-l", "-O", "\\t", "--ofs", ","];
+        assert_eq!(gen_step(do_l_post, &argv), expect);
+    }
+
+    #[test]
+    fn test_field_separator() {
+        let sep = |x| field_separator(&parse(&["-F", x]));
+        assert_eq!(field_separator(&parse(&[])), None);
+        assert_eq!(sep(" "), None);
+        assert_eq!(sep("."), Some((".".to_string(), false)));
+        assert_eq!(sep("::"), Some(("::".to_string(), false)));
+        assert_eq!(sep(":+"), Some((":+".to_string(), true)));
+        assert_eq!(sep("/ab/"), Some(("ab".to_string(), true)));
+    }
+
+    #[test]
+    fn test_record_separator() {
+        assert_eq!(record_separator(&parse(&[])), Ok(RecordSeparator::Newline));
+        assert_eq!(
+            record_separator(&parse(&["-z"])),
+            Ok(RecordSeparator::Char('\0'))
+        );
+        assert_eq!(
+            record_separator(&parse(&["--record-separator"])),
+            Ok(RecordSeparator::Char('\0'))
+        );
+        assert_eq!(
+            record_separator(&parse(&["-00"])),
+            Ok(RecordSeparator::Paragraph)
+        );
+        assert_eq!(
+            record_separator(&parse(&["-072"])),
+            Ok(RecordSeparator::Char(':'))
+        );
+        assert_eq!(
+            record_separator(&parse(&["-0777"])),
+            Ok(RecordSeparator::Slurp)
+        );
+        assert!(record_separator(&parse(&["-09"])).is_err());
+        assert!(record_separator(&parse(&["-0", "-z"])).is_err());
+    }
+
+    #[test]
+    fn test_variable() {
+        let var = |name: &str, value| Variable {
+            name: name.to_string(),
+            value,
+        };
+
+        assert_eq!(
+            variable("x=a=b"),
+            Ok(var("x", VariableValue::Str("a=b".to_string())))
+        );
+        assert_eq!(
+            variable("n:int=3"),
+            Ok(var("n", VariableValue::Int(types::LispAtom::Int(3))))
+        );
+        assert_eq!(
+            variable("n:int=-0b1010"),
+            Ok(var("n", VariableValue::Int(types::LispAtom::Int(-10))))
+        );
+        assert_eq!(
+            variable("n:int=123456789012345678901234567890"),
+            Ok(var(
+                "n",
+                VariableValue::Int(types::LispAtom::BigInt(
+                    "123456789012345678901234567890".to_string()
+                ))
+            ))
+        );
+        assert!(matches!(
+            variable("r:float=-inf"),
+            Ok(Variable { value: VariableValue::Float(x), .. }) if x == f64::NEG_INFINITY
+        ));
+        assert_eq!(
+            variable("r:float=0.5"),
+            Ok(var("r", VariableValue::Float(0.5)))
+        );
+        assert_eq!(
+            variable("ok:bool=false"),
+            Ok(var("ok", VariableValue::Bool(false)))
+        );
+        assert_eq!(
+            variable("xs:json=[1,2]"),
+            Ok(var("xs", VariableValue::Json("[1,2]".to_string())))
+        );
+        assert!(variable("x").is_err());
+        assert!(variable("1x=a").is_err());
+        assert!(variable("n:int=three").is_err());
+        assert!(variable("n:list=[]").is_err());
+    }
+}
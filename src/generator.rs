@@ -1,165 +1,1132 @@
+use crate::python_str;
 use crate::types;
 
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum GenErrorKind {
+    /// The car of a form does not name a known form.
+    UnknownForm(String),
+    WrongNumberOfArguments {
+        name: String,
+        min: usize,
+        max: Option<usize>,
+        actual: usize,
+    },
+    WrongTypeArgument {
+        expected: String,
+        actual: String,
+    },
+    /// A weak reference whose arena has been dropped.
+    DanglingReference,
+}
+
+/// Error from `gen`, with the path to the offending sub-expression.
+///
+/// Each index of `path` selects an element of a list, starting from the
+/// expression passed to `gen`; `[3, 1]` is the second element of the fourth
+/// element.
+#[derive(Debug, PartialEq)]
+pub struct GenError {
+    pub kind: GenErrorKind,
+    pub path: Vec<usize>,
+}
+
+impl GenError {
+    fn new(kind: GenErrorKind) -> Self {
+        GenError {
+            kind,
+            path: Vec::new(),
+        }
+    }
+
+    fn wrong_type(expected: &str, actual: &types::LispExp) -> Self {
+        let actual = match actual {
+            types::LispExp::Atom(atom) => atom.to_string(),
+            types::LispExp::Cons { .. } => "cons".to_string(),
+        };
+        GenError::new(GenErrorKind::WrongTypeArgument {
+            expected: expected.to_string(),
+            actual,
+        })
+    }
+
+    /// Locate the error inside the `index`th element of the enclosing list.
+    fn within(mut self, index: usize) -> Self {
+        self.path.insert(0, index);
+        self
+    }
+}
+
+impl fmt::Display for GenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            GenErrorKind::UnknownForm(name) => write!(f, "Unknown function: {}", name)?,
+            GenErrorKind::WrongNumberOfArguments {
+                name,
+                min,
+                max,
+                actual,
+            } => {
+                let max = match max {
+                    Some(max) => max.to_string(),
+                    None => "many".to_string(),
+                };
+                write!(
+                    f,
+                    "WrongNumberOfArguments: {}, expected: ({}, {}), actual: {}",
+                    name, min, max, actual
+                )?
+            }
+            GenErrorKind::WrongTypeArgument { expected, actual } => {
+                write!(f, "WrongTypeArgument: {}; {}", expected, actual)?
+            }
+            GenErrorKind::DanglingReference => write!(f, "DanglingReference")?,
+        }
+        if !self.path.is_empty() {
+            write!(f, " at {:?}", self.path)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for GenError {}
+
+type GenResult = Result<String, GenError>;
+
 fn indent(s: &str) -> String {
     s.lines()
-        .map(|x| format!("    {}", x))
+        .map(|x| match x.is_empty() {
+            true => String::new(),
+            false => format!("    {}", x),
+        })
         .collect::<Vec<String>>()
         .join("\n")
 }
 
-fn gen_atom(atom: &types::LispAtom) -> String {
-    match atom {
-        types::LispAtom::Symbol(s) => match &**s {
-            "t" => "True".to_string(),
-            "nil" => "None".to_string(),
-            "true" => "True".to_string(),
-            "false" => "False".to_string(),
-            _ => s.to_string(),
-        },
+fn upgrade(exp: &types::LispExpRef) -> Result<types::LispExpRefStrong, GenError> {
+    exp.upgrade()
+        .ok_or_else(|| GenError::new(GenErrorKind::DanglingReference))
+}
+
+fn is_nil(exp: &types::LispExpRef) -> Result<bool, GenError> {
+    let exp_ptr = upgrade(exp)?;
+    let x = matches!(
+        &*exp_ptr.borrow(),
+        types::LispExp::Atom(types::LispAtom::Symbol(s)) if s == "nil"
+    );
+    Ok(x)
+}
+
+/// Elements of the proper list `exp`.
+fn list_items(exp: &types::LispExpRef) -> Result<Vec<types::LispExpRef>, GenError> {
+    let mut items = Vec::new();
+    let mut cur = exp.clone();
+    loop {
+        let cur_ptr = upgrade(&cur).map_err(|e| e.within(items.len()))?;
+        let next = match &*cur_ptr.borrow() {
+            types::LispExp::Atom(types::LispAtom::Symbol(s)) if s == "nil" => None,
+            x @ types::LispExp::Atom(_) => return Err(GenError::wrong_type("list", x)),
+            types::LispExp::Cons { car, cdr } => {
+                items.push(car.clone());
+                Some(cdr.clone())
+            }
+        };
+        match next {
+            Some(next) => cur = next,
+            None => return Ok(items),
+        }
+    }
+}
+
+/// Arguments of the form `name`, of which there must be at least `min` and
+/// at most `max` (if any).
+fn form_args(
+    name: &str,
+    args: &types::LispExpRef,
+    min: usize,
+    max: Option<usize>,
+) -> Result<Vec<types::LispExpRef>, GenError> {
+    let items = list_items(args)?;
+    check_argc(name, &items, min, max)?;
+    Ok(items)
+}
+
+fn check_argc(
+    name: &str,
+    args: &[types::LispExpRef],
+    min: usize,
+    max: Option<usize>,
+) -> Result<(), GenError> {
+    if args.len() < min || matches!(max, Some(max) if args.len() > max) {
+        return Err(GenError::new(GenErrorKind::WrongNumberOfArguments {
+            name: name.to_string(),
+            min,
+            max,
+            actual: args.len(),
+        }));
+    }
+    Ok(())
+}
+
+/// Generate the `i`th argument of a form.
+fn gen_arg(args: &[types::LispExpRef], i: usize) -> GenResult {
+    gen(&args[i]).map_err(|e| e.within(i + 1))
+}
+
+/// Generate the arguments of a form starting from the `start`th.
+fn gen_args(args: &[types::LispExpRef], start: usize) -> Result<Vec<String>, GenError> {
+    (start..args.len()).map(|i| gen_arg(args, i)).collect()
+}
+
+/// Generate the arguments of a form from the `start`th on as the items of a
+/// call or display, where a yield expression must be parenthesized.
+fn gen_items(args: &[types::LispExpRef], start: usize) -> Result<Vec<String>, GenError> {
+    (start..args.len())
+        .map(|i| gen_operand(args, i, Prec::Stmt))
+        .collect()
+}
+
+/// Indented block of the statements from the `start`th argument on, `pass`
+/// when there are none.
+fn gen_block(args: &[types::LispExpRef], start: usize) -> GenResult {
+    let mut stmts = gen_args(args, start)?;
+    stmts.retain(|x| !x.is_empty());
+    match stmts.is_empty() {
+        true => Ok(indent("pass")),
+        false => Ok(indent(&stmts.join("\n"))),
+    }
+}
+
+/// Name of `exp` when it is a symbol.
+fn symbol_name(exp: &types::LispExpRef) -> Result<Option<String>, GenError> {
+    let exp_ptr = upgrade(exp)?;
+    let x = match &*exp_ptr.borrow() {
+        types::LispExp::Atom(types::LispAtom::Symbol(s)) => Some(s.clone()),
+        _ => None,
+    };
+    Ok(x)
+}
+
+/// Car of `exp` when it is a form headed by a symbol.
+fn form_name(exp: &types::LispExpRef) -> Result<Option<String>, GenError> {
+    let exp_ptr = upgrade(exp)?;
+    let x = match &*exp_ptr.borrow() {
+        types::LispExp::Cons { car, .. } => symbol_name(car)?,
+        types::LispExp::Atom(_) => None,
+    };
+    Ok(x)
+}
+
+/// Python operator precedence, from loosest to tightest binding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Prec {
+    /// A yield expression, which needs parentheses even as an argument.
+    Yield,
+    Stmt,
+    Lambda,
+    IfExp,
+    Or,
+    And,
+    Not,
+    Compare,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Shift,
+    Sum,
+    Term,
+    Unary,
+    Power,
+    Await,
+    Primary,
+    Atom,
+}
+
+impl Prec {
+    /// The next tighter precedence.
+    fn next(self) -> Prec {
+        const ALL: [Prec; 19] = [
+            Prec::Yield,
+            Prec::Stmt,
+            Prec::Lambda,
+            Prec::IfExp,
+            Prec::Or,
+            Prec::And,
+            Prec::Not,
+            Prec::Compare,
+            Prec::BitOr,
+            Prec::BitXor,
+            Prec::BitAnd,
+            Prec::Shift,
+            Prec::Sum,
+            Prec::Term,
+            Prec::Unary,
+            Prec::Power,
+            Prec::Await,
+            Prec::Primary,
+            Prec::Atom,
+        ];
+        ALL[(self as usize + 1).min(ALL.len() - 1)]
+    }
+}
+
+const COMPARE_OPS: [&str; 10] = [
+    "<", ">", "<=", ">=", "==", "!=", "in", "not-in", "is", "is-not",
+];
+
+/// Precedence of the binary operator `op`, if it is one.
+fn binary_prec(op: &str) -> Option<Prec> {
+    let x = match op {
+        "or" => Prec::Or,
+        "and" => Prec::And,
+        "|" => Prec::BitOr,
+        "^" => Prec::BitXor,
+        "&" => Prec::BitAnd,
+        "<<" | ">>" => Prec::Shift,
+        "+" | "-" => Prec::Sum,
+        "*" | "/" | "//" | "%" | "@" => Prec::Term,
+        "**" => Prec::Power,
+        op if COMPARE_OPS.contains(&op) => Prec::Compare,
+        _ => return None,
+    };
+    Some(x)
+}
+
+/// Precedence of the expression `exp` generates.
+fn precedence(exp: &types::LispExpRef) -> Result<Prec, GenError> {
+    let exp_ptr = upgrade(exp)?;
+    let x = match &*exp_ptr.borrow() {
+        types::LispExp::Atom(types::LispAtom::Int(i)) if *i < 0 => Prec::Unary,
+        types::LispExp::Atom(types::LispAtom::BigInt(i)) if i.starts_with('-') => Prec::Unary,
+        types::LispExp::Atom(types::LispAtom::Float(f))
+            if f.is_finite() && f.is_sign_negative() =>
+        {
+            Prec::Unary
+        }
+        types::LispExp::Atom(_) => Prec::Atom,
+        types::LispExp::Cons { .. } => {
+            let Some(name) = form_name(exp)? else {
+                return Ok(Prec::Atom);
+            };
+            let argc = list_items(exp)?.len() - 1;
+            match (&*name, argc) {
+                ("call" | "attr" | "subscript", _) => Prec::Primary,
+                ("list" | "tuple" | "dict" | "set", _) => Prec::Atom,
+                ("listcomp" | "setcomp" | "genexp" | "dictcomp", _) => Prec::Atom,
+                ("yield", _) => Prec::Yield,
+                ("lambda", _) => Prec::Lambda,
+                ("ifexp", _) => Prec::IfExp,
+                ("not", _) => Prec::Not,
+                ("compare", _) => Prec::Compare,
+                ("-" | "+" | "~", 1) => Prec::Unary,
+                ("*" | "**", 1) => Prec::Stmt,
+                (op, _) => binary_prec(op).unwrap_or(Prec::Stmt),
+            }
+        }
+    };
+    Ok(x)
+}
+
+/// Generate the `i`th argument of a form, parenthesized unless it binds at
+/// least as tightly as `min`.
+fn gen_operand(args: &[types::LispExpRef], i: usize, min: Prec) -> GenResult {
+    let x = gen_arg(args, i)?;
+    match precedence(&args[i]).map_err(|e| e.within(i + 1))? < min {
+        true => Ok(format!("({})", x)),
+        false => Ok(x),
+    }
+}
+
+fn gen_atom(atom: &types::LispAtom) -> GenResult {
+    let x = match atom {
+        types::LispAtom::Symbol(s) => s.to_string(),
+        types::LispAtom::Bool(true) => "True".to_string(),
+        types::LispAtom::Bool(false) => "False".to_string(),
+        types::LispAtom::None => "None".to_string(),
+        // Rust's debug format of a finite float is a valid Python float
+        // literal, exponent included; the others have no literal.
+        types::LispAtom::Float(f) if f.is_nan() => "float(\"nan\")".to_string(),
+        types::LispAtom::Float(f) if f.is_infinite() && *f > 0.0 => "float(\"inf\")".to_string(),
+        types::LispAtom::Float(f) if f.is_infinite() => "float(\"-inf\")".to_string(),
+        types::LispAtom::Float(f) => format!("{:?}", f),
+        // Raw literals keep regexes and Windows paths readable.
+        types::LispAtom::String(s) => python_str::str_literal(s, s.contains('\\')),
+        types::LispAtom::Bytes(b) => python_str::bytes_literal(b, b.contains(&b'\\')),
+        types::LispAtom::FString(parts) => gen_fstring(parts)?,
+        types::LispAtom::RawText(code) => code.to_string(),
         _ => atom.to_string(),
+    };
+    Ok(x)
+}
+
+fn gen_fstring(parts: &[types::FStringPart]) -> GenResult {
+    let mut codes = Vec::new();
+    for part in parts {
+        if let types::FStringPart::Exp { exp, .. } = part {
+            // A lambda's colon or a display's opening brace would be taken
+            // for part of the replacement field.
+            let code = gen(exp)?;
+            let code = match precedence(exp)? < Prec::IfExp || code.starts_with('{') {
+                true => format!("({})", code),
+                false => code,
+            };
+            codes.push(code);
+        }
     }
+
+    let mut codes = codes.iter();
+    let fields = parts
+        .iter()
+        .map(|x| match x {
+            types::FStringPart::Text(t) => python_str::FStringField::Text(t),
+            types::FStringPart::Exp { spec, .. } => {
+                python_str::FStringField::Exp(codes.next().unwrap(), spec.as_deref())
+            }
+        })
+        .collect::<Vec<_>>();
+    Ok(python_str::fstring_literal(&fields))
 }
 
-fn gen_cons(car: &types::LispExpRef, cdr: &types::LispExpRef) -> String {
-    let car_ptr = car.upgrade().unwrap();
+fn gen_cons(car: &types::LispExpRef, cdr: &types::LispExpRef) -> GenResult {
+    let car_ptr = upgrade(car).map_err(|e| e.within(0))?;
     let fn_ = match &*car_ptr.borrow() {
         types::LispExp::Atom(types::LispAtom::Symbol(s)) => s.clone(),
-        _ => panic!("WrongTypeArgument: atom; car"),
+        x => return Err(GenError::wrong_type("symbol", x).within(0)),
     };
 
-    match &*fn_ {
-        "kw" => gen_cons_kw(cdr),
-        "call" => gen_cons_call(cdr),
-        "for" => gen_cons_for(cdr),
-        "with" => gen_cons_with(cdr),
-        "progn" => gen_cons_progn(cdr),
-        "attr" => gen_cons_attr(cdr),
-        "assign" => gen_cons_assign(cdr),
-        "import" => gen_cons_import(cdr),
-        "lambda" => gen_cons_lambda(cdr),
-        "*" => gen_cons_star(cdr),
-        "**" => gen_cons_dstar(cdr),
-        _ => panic!("Unknown function: {}", fn_),
+    match form(&fn_) {
+        Some(gen_form) => gen_form(&fn_, cdr),
+        None => Err(GenError::new(GenErrorKind::UnknownForm(fn_))),
     }
 }
 
-fn gen_cons_kw(args: &types::LispExpRef) -> String {
-    let args_ptr = args.upgrade().unwrap();
+/// Generator for the form `name`, called with the name and the arguments.
+fn form(name: &str) -> Option<fn(&str, &types::LispExpRef) -> GenResult> {
+    let x: fn(&str, &types::LispExpRef) -> GenResult = match name {
+        "kw" => |_, args| gen_cons_kw(args),
+        "call" => |_, args| gen_cons_call(args),
+        "for" => |_, args| gen_cons_for(args),
+        "while" => |_, args| gen_cons_while(args),
+        "break" | "continue" | "pass" => gen_cons_keyword,
+        "with" => |_, args| gen_cons_with(args),
+        "progn" => |_, args| gen_cons_progn(args),
+        "attr" => |_, args| gen_cons_attr(args),
+        "subscript" => |_, args| gen_cons_subscript(args),
+        "list" => |_, args| gen_cons_list(args),
+        "tuple" => |_, args| gen_cons_tuple(args),
+        "dict" => |_, args| gen_cons_dict(args),
+        "set" => |_, args| gen_cons_set(args),
+        "slice" => |_, args| gen_cons_slice(args),
+        "listcomp" => |_, args| gen_cons_comp("listcomp", "[", "]", args),
+        "setcomp" => |_, args| gen_cons_comp("setcomp", "{", "}", args),
+        "genexp" => |_, args| gen_cons_comp("genexp", "(", ")", args),
+        "dictcomp" => |_, args| gen_cons_dictcomp(args),
+        "assign" => |_, args| gen_cons_assign(args),
+        "import" => |_, args| gen_cons_import(args),
+        "lambda" => |_, args| gen_cons_lambda(args),
+        "if" => |_, args| gen_cons_if(args),
+        "when" => |_, args| gen_cons_when(args),
+        "unless" => |_, args| gen_cons_unless(args),
+        "ifexp" => |_, args| gen_cons_ifexp(args),
+        "def" => |_, args| gen_cons_def(args),
+        "return" => |_, args| gen_cons_return(args),
+        "yield" => |_, args| gen_cons_yield(args),
+        "class" => |_, args| gen_cons_class(args),
+        "decorate" => |_, args| gen_cons_decorate(args),
+        "try" => |_, args| gen_cons_try(args),
+        "raise" => |_, args| gen_cons_raise(args),
+        "assert" => |_, args| gen_cons_assert(args),
+        "*" => |_, args| gen_cons_star(args),
+        "**" => |_, args| gen_cons_dstar(args),
+        "-" | "+" | "~" => gen_cons_unary,
+        "not" => |_, args| gen_cons_not(args),
+        "compare" => |_, args| gen_cons_compare(args),
+        op if binary_prec(op).is_some() => gen_cons_binary,
+        _ => return None,
+    };
+    Some(x)
+}
+
+fn gen_cons_kw(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("kw", args, 2, Some(2))?;
+
+    Ok(format!(
+        "{}={}",
+        gen_arg(&args, 0)?,
+        gen_operand(&args, 1, Prec::Stmt)?
+    ))
+}
+
+fn gen_cons_call(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("call", args, 1, None)?;
+    let mut fn_args_str = gen_items(&args, 1)?.join(", ");
+    // A generator expression as the only argument needs no parentheses of
+    // its own.
+    if args.len() == 2 && form_name(&args[1]).map_err(|e| e.within(2))?.as_deref() == Some("genexp")
+    {
+        fn_args_str = fn_args_str[1..fn_args_str.len() - 1].to_string();
+    }
+
+    Ok(format!(
+        "{}({})",
+        gen_operand(&args, 0, Prec::Primary)?,
+        fn_args_str
+    ))
+}
 
-    let mut arg_iter = args_ptr.borrow().iter();
-    let v1 = arg_iter.next().unwrap();
-    let v2 = arg_iter.next().unwrap();
+/// Shape of an assignment target.
+enum Target {
+    /// An atom or an `attr`/`subscript`/`*` form.
+    Single,
+    /// Targets to destructure into, starting at the given index: a bare
+    /// list, or a `tuple` form.
+    Tuple(usize),
+    /// A `list` form, written `[a, b]`.
+    List,
+}
 
-    format!("{}={}", gen(&v1), gen(&v2))
+fn target_kind(exp: &types::LispExpRef) -> Result<Target, GenError> {
+    let exp_ptr = upgrade(exp)?;
+    if matches!(&*exp_ptr.borrow(), types::LispExp::Atom(..)) {
+        return Ok(Target::Single);
+    }
+    let x = match form_name(exp)?.as_deref() {
+        Some("attr" | "subscript" | "*") => Target::Single,
+        Some("tuple") => Target::Tuple(1),
+        Some("list") => Target::List,
+        // Any other form is an expression, never a target.
+        Some(name) if form(name).is_some() => {
+            return Err(GenError::wrong_type("assignment target", &exp_ptr.borrow()));
+        }
+        _ => Target::Tuple(0),
+    };
+    Ok(x)
 }
 
-fn gen_cons_call(args: &types::LispExpRef) -> String {
-    let args_ptr = args.upgrade().unwrap();
+/// Assignment target: an atom, an `attr`/`subscript`/`*` form, or targets
+/// to destructure into, given as a bare list or a `tuple` or `list` form
+/// (nested tuples are parenthesized).
+fn gen_target(exp: &types::LispExpRef) -> GenResult {
+    let start = match target_kind(exp)? {
+        Target::Single => return gen(exp),
+        Target::Tuple(start) => start,
+        Target::List => 1,
+    };
 
-    let mut arg_iter = args_ptr.borrow().iter();
-    let fn_ = arg_iter.next().unwrap();
-    let fn_args_str = arg_iter.map(|x| gen(&x)).collect::<Vec<_>>().join(", ");
+    let items = list_items(exp)?;
+    let strs = (start..items.len())
+        .map(|i| {
+            let x = gen_target(&items[i]).map_err(|e| e.within(i))?;
+            match target_kind(&items[i])? {
+                Target::Tuple(_) => Ok(format!("({})", x)),
+                _ => Ok(x),
+            }
+        })
+        .collect::<Result<Vec<_>, GenError>>()?;
+    match (target_kind(exp)?, strs.len()) {
+        (Target::List, _) => Ok(format!("[{}]", strs.join(", "))),
+        (_, 0) => Ok("()".to_string()),
+        (_, 1) => Ok(format!("{},", strs[0])),
+        _ => Ok(strs.join(", ")),
+    }
+}
 
-    format!("{}({})", gen(&fn_), fn_args_str)
+/// `else:` clause of a loop, empty when the form has no `index`th argument.
+fn gen_loop_else(args: &[types::LispExpRef], index: usize) -> GenResult {
+    match args.len() > index {
+        true => Ok(format!("\nelse:\n{}", gen_block(args, index)?)),
+        false => Ok(String::new()),
+    }
 }
 
-fn gen_cons_for(args: &types::LispExpRef) -> String {
-    let args_ptr = args.upgrade().unwrap();
+fn gen_cons_for(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("for", args, 3, Some(4))?;
 
-    let mut arg_iter = args_ptr.borrow().iter();
-    let v1 = arg_iter.next().unwrap();
-    let v2 = arg_iter.next().unwrap();
-    let v3 = arg_iter.next().unwrap();
+    Ok(format!(
+        "for {} in {}:\n{}{}",
+        gen_target(&args[0]).map_err(|e| e.within(1))?,
+        gen_arg(&args, 1)?,
+        gen_block(&args[..3], 2)?,
+        gen_loop_else(&args, 3)?
+    ))
+}
+
+fn gen_cons_while(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("while", args, 2, Some(3))?;
 
-    format!("for {} in {}:\n{}", gen(&v1), gen(&v2), indent(&gen(&v3)))
+    Ok(format!(
+        "while {}:\n{}{}",
+        gen_arg(&args, 0)?,
+        gen_block(&args[..2], 1)?,
+        gen_loop_else(&args, 2)?
+    ))
 }
 
-fn gen_cons_with(args: &types::LispExpRef) -> String {
-    let args_ptr = args.upgrade().unwrap();
+/// Forms without arguments that generate a single keyword statement.
+fn gen_cons_keyword(name: &str, args: &types::LispExpRef) -> GenResult {
+    form_args(name, args, 0, Some(0))?;
+
+    Ok(name.to_string())
+}
 
-    let mut arg_iter = args_ptr.borrow().iter();
-    let v1 = arg_iter.next().unwrap();
-    let v2 = arg_iter.next().unwrap();
-    let v3 = arg_iter.next().unwrap();
+fn gen_cons_with(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("with", args, 3, Some(3))?;
 
-    format!("with {} as {}:\n{}", gen(&v1), gen(&v2), indent(&gen(&v3)))
+    let body = gen_block(&args, 2)?;
+    match is_nil(&args[1]).map_err(|e| e.within(2))? {
+        true => Ok(format!("with {}:\n{}", gen_arg(&args, 0)?, body)),
+        false => Ok(format!(
+            "with {} as {}:\n{}",
+            gen_arg(&args, 0)?,
+            gen_arg(&args, 1)?,
+            body
+        )),
+    }
 }
 
-fn gen_cons_progn(args: &types::LispExpRef) -> String {
-    let args_ptr = args.upgrade().unwrap();
+fn gen_cons_progn(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("progn", args, 0, None)?;
 
-    let arg_iter = args_ptr.borrow().iter();
-    arg_iter.map(|x| gen(&x)).collect::<Vec<_>>().join("\n")
+    Ok(gen_args(&args, 0)?.join("\n"))
 }
 
-fn gen_cons_attr(args: &types::LispExpRef) -> String {
-    let args_ptr = args.upgrade().unwrap();
+fn gen_cons_attr(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("attr", args, 2, Some(2))?;
 
-    let mut arg_iter = args_ptr.borrow().iter();
-    let v1 = arg_iter.next().unwrap();
-    let v2 = arg_iter.next().unwrap();
+    // `1.real` would read as a float literal followed by a name.
+    let obj_ptr = upgrade(&args[0]).map_err(|e| e.within(1))?;
+    let obj = match &*obj_ptr.borrow() {
+        types::LispExp::Atom(types::LispAtom::Int(i)) if *i >= 0 => format!("({})", i),
+        types::LispExp::Atom(types::LispAtom::BigInt(i)) if !i.starts_with('-') => {
+            format!("({})", i)
+        }
+        _ => gen_operand(&args, 0, Prec::Primary)?,
+    };
+    Ok(format!("{}.{}", obj, gen_arg(&args, 1)?))
+}
 
-    format!("{}.{}", gen(&v1), gen(&v2))
+fn gen_cons_subscript(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("subscript", args, 2, None)?;
+    let index_str = gen_items(&args, 1)?.join(", ");
+
+    Ok(format!(
+        "{}[{}]",
+        gen_operand(&args, 0, Prec::Primary)?,
+        index_str
+    ))
 }
 
-fn gen_cons_assign(args: &types::LispExpRef) -> String {
-    let args_ptr = args.upgrade().unwrap();
+fn gen_cons_list(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("list", args, 0, None)?;
+
+    Ok(format!("[{}]", gen_items(&args, 0)?.join(", ")))
+}
 
-    let mut arg_iter = args_ptr.borrow().iter();
-    let v1 = arg_iter.next().unwrap();
-    let v2 = arg_iter.next().unwrap();
+fn gen_cons_tuple(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("tuple", args, 0, None)?;
 
-    format!("{} = {}", gen(&v1), gen(&v2))
+    match &gen_items(&args, 0)?[..] {
+        [x] => Ok(format!("({},)", x)),
+        xs => Ok(format!("({})", xs.join(", "))),
+    }
 }
 
-fn gen_cons_import(args: &types::LispExpRef) -> String {
-    let args_ptr = args.upgrade().unwrap();
+/// `(dict k1 v1 k2 v2 (** d))`: keys and values alternate, and a `**` form
+/// stands alone.
+fn gen_cons_dict(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("dict", args, 0, None)?;
 
-    let mut arg_iter = args_ptr.borrow().iter();
-    let v1 = arg_iter.next().unwrap();
+    let mut items = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        let is_unpack = form_name(&args[i]).map_err(|e| e.within(i + 1))?.as_deref() == Some("**")
+            && list_items(&args[i]).map_err(|e| e.within(i + 1))?.len() == 2;
+        if is_unpack {
+            items.push(gen_arg(&args, i)?);
+            i += 1;
+            continue;
+        }
+        if i + 1 == args.len() {
+            let key_ptr = upgrade(&args[i]).map_err(|e| e.within(i + 1))?;
+            let err = GenError::wrong_type("key and value", &key_ptr.borrow());
+            return Err(err.within(i + 1));
+        }
+        items.push(format!(
+            "{}: {}",
+            gen_operand(&args, i, Prec::IfExp)?,
+            gen_operand(&args, i + 1, Prec::Stmt)?
+        ));
+        i += 2;
+    }
+    Ok(format!("{{{}}}", items.join(", ")))
+}
+
+fn gen_cons_set(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("set", args, 0, None)?;
 
-    format!("import {}", gen(&v1))
+    match args.is_empty() {
+        true => Ok("set()".to_string()),
+        false => Ok(format!("{{{}}}", gen_items(&args, 0)?.join(", "))),
+    }
 }
 
-fn gen_cons_lambda(args: &types::LispExpRef) -> String {
-    let args_ptr = args.upgrade().unwrap();
+/// `(slice START STOP STEP)` for use in `subscript`; `nil` or missing parts
+/// are left out, so `(slice nil nil -1)` is `::-1`.
+fn gen_cons_slice(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("slice", args, 0, Some(3))?;
 
-    let mut arg_iter = args_ptr.borrow().iter();
-    let v1 = arg_iter.next().unwrap();
-    let v2 = arg_iter.next().unwrap();
+    let mut parts = vec![String::new(); 2];
+    for i in 0..args.len() {
+        if is_nil(&args[i]).map_err(|e| e.within(i + 1))? {
+            continue;
+        }
+        let x = gen_operand(&args, i, Prec::IfExp)?;
+        match i < parts.len() {
+            true => parts[i] = x,
+            false => parts.push(x),
+        }
+    }
+    Ok(parts.join(":"))
+}
 
-    let largs = match &*v1.upgrade().unwrap().borrow() {
-        types::LispExp::Atom(..) => gen(&v1),
-        types::LispExp::Cons { .. } => {
-            let v1_iter = v1.upgrade().unwrap().borrow().iter();
-            v1_iter.map(|x| gen(&x)).collect::<Vec<_>>().join(", ")
+/// `for` and `if` clauses of a comprehension, from the `start`th argument
+/// on; the first must be a `for`.
+fn gen_comp_clauses(args: &[types::LispExpRef], start: usize) -> GenResult {
+    let mut clauses = Vec::new();
+    for (i, clause) in args.iter().enumerate().skip(start) {
+        let x = || -> GenResult {
+            let name = form_name(clause)?;
+            let items = list_items(clause)?;
+            let cargs = &items[1..];
+            match name.as_deref() {
+                Some("for") => {
+                    check_argc("for", cargs, 2, Some(2))?;
+                    Ok(format!(
+                        "for {} in {}",
+                        gen_target(&cargs[0]).map_err(|e| e.within(1))?,
+                        gen_operand(cargs, 1, Prec::Or)?
+                    ))
+                }
+                Some("if") if i > start => {
+                    check_argc("if", cargs, 1, Some(1))?;
+                    Ok(format!("if {}", gen_operand(cargs, 0, Prec::Or)?))
+                }
+                _ => {
+                    let expected = match i > start {
+                        true => "for or if clause",
+                        false => "for clause",
+                    };
+                    let clause_ptr = upgrade(clause)?;
+                    let err = GenError::wrong_type(expected, &clause_ptr.borrow());
+                    Err(err)
+                }
+            }
+        };
+        clauses.push(x().map_err(|e| e.within(i + 1))?);
+    }
+    Ok(clauses.join(" "))
+}
+
+/// `(listcomp ELT CLAUSE...)` and the like, where each clause is
+/// `(for TARGET ITER)` or `(if COND)`.
+fn gen_cons_comp(name: &str, open: &str, close: &str, args: &types::LispExpRef) -> GenResult {
+    let args = form_args(name, args, 2, None)?;
+
+    Ok(format!(
+        "{}{} {}{}",
+        open,
+        gen_operand(&args, 0, Prec::Lambda)?,
+        gen_comp_clauses(&args, 1)?,
+        close
+    ))
+}
+
+/// `(dictcomp KEY VALUE CLAUSE...)`.
+fn gen_cons_dictcomp(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("dictcomp", args, 3, None)?;
+
+    Ok(format!(
+        "{{{}: {} {}}}",
+        gen_operand(&args, 0, Prec::IfExp)?,
+        gen_operand(&args, 1, Prec::Lambda)?,
+        gen_comp_clauses(&args, 2)?
+    ))
+}
+
+fn gen_cons_assign(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("assign", args, 2, Some(2))?;
+
+    Ok(format!("{} = {}", gen_arg(&args, 0)?, gen_arg(&args, 1)?))
+}
+
+fn gen_cons_import(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("import", args, 1, Some(1))?;
+
+    Ok(format!("import {}", gen_arg(&args, 0)?))
+}
+
+/// Parameter list of a lambda: a single atom, or a list of parameters.
+fn gen_params(params: &types::LispExpRef) -> GenResult {
+    let params_ptr = upgrade(params)?;
+    let is_atom = matches!(&*params_ptr.borrow(), types::LispExp::Atom(..));
+    if is_atom && !is_nil(params)? {
+        return gen(params);
+    }
+
+    let items = list_items(params)?;
+    let strs = (0..items.len())
+        .map(|i| gen(&items[i]).map_err(|e| e.within(i)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(strs.join(", "))
+}
+
+fn gen_cons_lambda(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("lambda", args, 2, Some(2))?;
+
+    let largs = gen_params(&args[0]).map_err(|e| e.within(1))?;
+    match largs.is_empty() {
+        true => Ok(format!("lambda: {}", gen_arg(&args, 1)?)),
+        false => Ok(format!("lambda {}: {}", largs, gen_arg(&args, 1)?)),
+    }
+}
+
+fn gen_cons_if(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("if", args, 2, None)?;
+
+    let head = format!("if {}:\n{}", gen_arg(&args, 0)?, gen_block(&args[..2], 1)?);
+    if args.len() == 2 {
+        return Ok(head);
+    }
+
+    // A lone `if` in the else branch continues the chain as `elif`.
+    let is_elif = args.len() == 3 && form_name(&args[2])?.as_deref() == Some("if");
+    match is_elif {
+        true => Ok(format!("{}\nel{}", head, gen_arg(&args, 2)?)),
+        false => Ok(format!("{}\nelse:\n{}", head, gen_block(&args, 2)?)),
+    }
+}
+
+fn gen_cons_when(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("when", args, 1, None)?;
+
+    Ok(format!(
+        "if {}:\n{}",
+        gen_arg(&args, 0)?,
+        gen_block(&args, 1)?
+    ))
+}
+
+fn gen_cons_unless(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("unless", args, 1, None)?;
+
+    Ok(format!(
+        "if not {}:\n{}",
+        gen_operand(&args, 0, Prec::Not)?,
+        gen_block(&args, 1)?
+    ))
+}
+
+fn gen_cons_ifexp(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("ifexp", args, 3, Some(3))?;
+
+    Ok(format!(
+        "{} if {} else {}",
+        gen_operand(&args, 1, Prec::Or)?,
+        gen_operand(&args, 0, Prec::Or)?,
+        gen_operand(&args, 2, Prec::IfExp)?
+    ))
+}
+
+/// Body of a `def` or `class`; a leading string becomes the docstring.
+fn gen_def_body(args: &[types::LispExpRef], start: usize) -> GenResult {
+    let doc = match args.get(start) {
+        Some(exp) => {
+            let exp_ptr = upgrade(exp).map_err(|e| e.within(start + 1))?;
+            let x = match &*exp_ptr.borrow() {
+                types::LispExp::Atom(types::LispAtom::String(s)) => Some(s.clone()),
+                _ => None,
+            };
+            x
+        }
+        None => None,
+    };
+    let Some(doc) = doc else {
+        return gen_block(args, start);
+    };
+
+    let doc = indent(&python_str::docstring_literal(&doc));
+    match args.len() > start + 1 {
+        true => Ok(format!("{}\n{}", doc, gen_block(args, start + 1)?)),
+        false => Ok(doc),
+    }
+}
+
+fn gen_cons_def(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("def", args, 2, None)?;
+
+    Ok(format!(
+        "def {}({}):\n{}",
+        gen_arg(&args, 0)?,
+        gen_params(&args[1]).map_err(|e| e.within(2))?,
+        gen_def_body(&args, 2)?
+    ))
+}
+
+fn gen_cons_return(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("return", args, 0, Some(1))?;
+
+    match args.is_empty() {
+        true => Ok("return".to_string()),
+        false => Ok(format!("return {}", gen_arg(&args, 0)?)),
+    }
+}
+
+fn gen_cons_yield(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("yield", args, 0, Some(1))?;
+
+    match args.is_empty() {
+        true => Ok("yield".to_string()),
+        false => Ok(format!("yield {}", gen_arg(&args, 0)?)),
+    }
+}
+
+fn gen_cons_class(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("class", args, 2, None)?;
+
+    let bases = list_items(&args[1]).map_err(|e| e.within(2))?;
+    let bases = (0..bases.len())
+        .map(|i| gen(&bases[i]).map_err(|e| e.within(i).within(2)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let name = gen_arg(&args, 0)?;
+    let head = match bases.is_empty() {
+        true => name,
+        false => format!("{}({})", name, bases.join(", ")),
+    };
+    Ok(format!("class {}:\n{}", head, gen_def_body(&args, 2)?))
+}
+
+fn gen_cons_decorate(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("decorate", args, 1, None)?;
+
+    let last = args.len() - 1;
+    let decorators = gen_args(&args[..last], 0)?;
+    let decorators = decorators
+        .iter()
+        .map(|x| format!("@{}\n", x))
+        .collect::<String>();
+    Ok(format!("{}{}", decorators, gen_arg(&args, last)?))
+}
+
+/// `except`, `else` or `finally` clause of a `try` form.
+fn gen_try_clause(exp: &types::LispExpRef) -> GenResult {
+    let invalid = || -> GenError {
+        match upgrade(exp) {
+            Ok(exp_ptr) => {
+                GenError::wrong_type("except, else or finally clause", &exp_ptr.borrow())
+            }
+            Err(err) => err,
         }
     };
+    let Some(name) = form_name(exp)? else {
+        return Err(invalid());
+    };
+    let args = list_items(exp)?[1..].to_vec();
+    match &*name {
+        "except" => (),
+        "else" | "finally" => return Ok(format!("{}:\n{}", name, gen_block(&args, 0)?)),
+        _ => return Err(invalid()),
+    }
+
+    let type_ = match args.first() {
+        Some(type_) if !is_nil(type_).map_err(|e| e.within(1))? => Some(gen_arg(&args, 0)?),
+        _ => None,
+    };
+    let var = match args.get(1) {
+        Some(var) if !is_nil(var).map_err(|e| e.within(2))? => Some(gen_arg(&args, 1)?),
+        _ => None,
+    };
+    let head = match (type_, var) {
+        (None, None) => "except".to_string(),
+        (Some(type_), None) => format!("except {}", type_),
+        (Some(type_), Some(var)) => format!("except {} as {}", type_, var),
+        (None, Some(_)) => {
+            let var_ptr = upgrade(&args[1])?;
+            let err = GenError::wrong_type("nil", &var_ptr.borrow());
+            return Err(err.within(2));
+        }
+    };
+    Ok(format!("{}:\n{}", head, gen_block(&args, 2)?))
+}
+
+fn gen_cons_try(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("try", args, 2, None)?;
+
+    let mut lines = vec![format!("try:\n{}", gen_block(&args[..1], 0)?)];
+    let mut prev = None;
+    for (i, clause) in args.iter().enumerate().skip(1) {
+        lines.push(gen_try_clause(clause).map_err(|e| e.within(i + 1))?);
+
+        // Clauses go except..., else, finally, with an except or a finally
+        // at least, and an else only after an except.
+        let name = form_name(clause)?;
+        let in_order = matches!(
+            (prev.as_deref(), name.as_deref()),
+            (None, Some("except" | "finally"))
+                | (Some("except"), Some("except" | "else" | "finally"))
+                | (Some("else"), Some("finally"))
+        );
+        if !in_order {
+            let expected = match prev.as_deref() {
+                None => "except or finally clause",
+                Some("except") => "except, else or finally clause",
+                Some("else") => "finally clause",
+                _ => "end of try",
+            };
+            let clause_ptr = upgrade(clause)?;
+            let err = GenError::wrong_type(expected, &clause_ptr.borrow());
+            return Err(err.within(i + 1));
+        }
+        prev = name;
+    }
+    Ok(lines.join("\n"))
+}
+
+fn gen_cons_raise(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("raise", args, 0, Some(2))?;
+
+    match args.len() {
+        0 => Ok("raise".to_string()),
+        1 => Ok(format!("raise {}", gen_arg(&args, 0)?)),
+        _ => Ok(format!(
+            "raise {} from {}",
+            gen_arg(&args, 0)?,
+            gen_arg(&args, 1)?
+        )),
+    }
+}
+
+fn gen_cons_assert(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("assert", args, 1, Some(2))?;
+
+    match args.len() {
+        1 => Ok(format!("assert {}", gen_arg(&args, 0)?)),
+        _ => Ok(format!(
+            "assert {}, {}",
+            gen_arg(&args, 0)?,
+            gen_arg(&args, 1)?
+        )),
+    }
+}
+
+/// `(* x)` unpacks, `(* a b ...)` multiplies.
+fn gen_cons_star(args: &types::LispExpRef) -> GenResult {
+    let items = form_args("*", args, 1, None)?;
+
+    match items.len() {
+        1 => Ok(format!("*{}", gen_operand(&items, 0, Prec::BitOr)?)),
+        _ => gen_cons_binary("*", args),
+    }
+}
+
+/// `(** x)` unpacks, `(** a b ...)` raises to a power.
+fn gen_cons_dstar(args: &types::LispExpRef) -> GenResult {
+    let items = form_args("**", args, 1, None)?;
+
+    match items.len() {
+        1 => Ok(format!("**{}", gen_operand(&items, 0, Prec::BitOr)?)),
+        _ => gen_cons_binary("**", args),
+    }
+}
+
+/// `(- x)`, `(+ x)` and `(~ x)`; `-` and `+` with more arguments are binary.
+fn gen_cons_unary(op: &str, args: &types::LispExpRef) -> GenResult {
+    let max = match op {
+        "~" => Some(1),
+        _ => None,
+    };
+    let items = form_args(op, args, 1, max)?;
 
-    format!("lambda {}: {}", largs, gen(&v2))
+    match items.len() {
+        1 => Ok(format!("{}{}", op, gen_operand(&items, 0, Prec::Unary)?)),
+        _ => gen_cons_binary(op, args),
+    }
 }
 
-fn gen_cons_star(args: &types::LispExpRef) -> String {
-    let args_ptr = args.upgrade().unwrap();
+fn gen_cons_not(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("not", args, 1, Some(1))?;
 
-    let mut arg_iter = args_ptr.borrow().iter();
-    let v1 = arg_iter.next().unwrap();
+    Ok(format!("not {}", gen_operand(&args, 0, Prec::Not)?))
+}
 
-    format!("*{}", gen(&v1))
+/// Python spelling of a comparison operator symbol.
+fn compare_op(op: &str) -> &str {
+    match op {
+        "not-in" => "not in",
+        "is-not" => "is not",
+        _ => op,
+    }
 }
 
-fn gen_cons_dstar(args: &types::LispExpRef) -> String {
-    let args_ptr = args.upgrade().unwrap();
+/// Binary operators over two or more operands: `(+ a b c)` is `a + b + c`
+/// and `(< a b c)` the chained comparison `a < b < c`.
+fn gen_cons_binary(op: &str, args: &types::LispExpRef) -> GenResult {
+    let args = form_args(op, args, 2, None)?;
+    let prec = binary_prec(op).unwrap();
 
-    let mut arg_iter = args_ptr.borrow().iter();
-    let v1 = arg_iter.next().unwrap();
+    let last = args.len() - 1;
+    let operands = (0..args.len())
+        .map(|i| {
+            // `**` is right associative and takes a unary operand on its
+            // right.  Comparisons chain rather than nest.
+            let min = match (prec, i) {
+                (Prec::Power, i) if i == last => Prec::Unary,
+                (Prec::Power, _) => Prec::Await,
+                (Prec::Compare, _) => Prec::BitOr,
+                (_, 0) => prec,
+                _ => prec.next(),
+            };
+            gen_operand(&args, i, min)
+        })
+        .collect::<Result<Vec<_>, GenError>>()?;
+    Ok(operands.join(&format!(" {} ", compare_op(op))))
+}
 
-    format!("**{}", gen(&v1))
+/// `(compare a < b <= c)`: a chained comparison with mixed operators.
+fn gen_cons_compare(args: &types::LispExpRef) -> GenResult {
+    let args = form_args("compare", args, 3, None)?;
+    if args.len() % 2 == 0 {
+        return Err(GenError::new(GenErrorKind::WrongNumberOfArguments {
+            name: "compare".to_string(),
+            min: 3,
+            max: None,
+            actual: args.len(),
+        }));
+    }
+
+    let mut strs = vec![gen_operand(&args, 0, Prec::BitOr)?];
+    for i in (1..args.len()).step_by(2) {
+        let op = match symbol_name(&args[i]).map_err(|e| e.within(i + 1))? {
+            Some(op) if COMPARE_OPS.contains(&&*op) => op,
+            _ => {
+                let op_ptr = upgrade(&args[i])?;
+                let err = GenError::wrong_type("comparison operator", &op_ptr.borrow());
+                return Err(err.within(i + 1));
+            }
+        };
+        strs.push(compare_op(&op).to_string());
+        strs.push(gen_operand(&args, i + 1, Prec::BitOr)?);
+    }
+    Ok(strs.join(" "))
 }
 
-pub fn gen(exp: &types::LispExpRef) -> String {
-    let exp_ptr = exp.upgrade().unwrap();
+pub fn gen(exp: &types::LispExpRef) -> Result<String, GenError> {
+    let exp_ptr = upgrade(exp)?;
     let x = match &*exp_ptr.borrow() {
+        // nil stands for an omitted part only where a form allows one.
+        x @ types::LispExp::Atom(types::LispAtom::Symbol(s)) if s == "nil" => {
+            Err(GenError::wrong_type("expression", x))
+        }
         types::LispExp::Atom(atom) => gen_atom(atom),
         types::LispExp::Cons { car, cdr } => gen_cons(car, cdr),
     };
@@ -170,33 +1137,45 @@ pub fn gen(exp: &types::LispExpRef) -> String {
 mod tests {
     use super::*;
 
+    fn gen_str(src: &str) -> String {
+        let mut arena = types::LispArena::default();
+        let e = crate::reader::read(src, &mut arena).unwrap();
+        gen(&e).unwrap()
+    }
+
     #[test]
     fn test_gen_atom() {
+        // Only the dedicated atoms are constants; these are plain names.
         assert_eq!(
-            gen_atom(&types::LispAtom::new_symbol("t")),
-            "True".to_string()
+            gen_atom(&types::LispAtom::new_symbol("t")).unwrap(),
+            "t".to_string()
         );
         assert_eq!(
-            gen_atom(&types::LispAtom::new_symbol("nil")),
-            "None".to_string()
+            gen_atom(&types::LispAtom::new_symbol("true")).unwrap(),
+            "true".to_string()
         );
         assert_eq!(
-            gen_atom(&types::LispAtom::new_symbol("true")),
-            "True".to_string()
+            gen_atom(&types::LispAtom::new_symbol("foo")).unwrap(),
+            "foo".to_string()
         );
+        assert_eq!(gen_atom(&types::LispAtom::Int(1)).unwrap(), "1".to_string());
         assert_eq!(
-            gen_atom(&types::LispAtom::new_symbol("false")),
-            "False".to_string()
+            gen_atom(&types::LispAtom::Float(1.1)).unwrap(),
+            "1.1".to_string()
         );
         assert_eq!(
-            gen_atom(&types::LispAtom::new_symbol("foo")),
-            "foo".to_string()
+            gen_atom(&types::LispAtom::String("foo".to_string())).unwrap(),
+            "\"foo\"".to_string()
         );
-        assert_eq!(gen_atom(&types::LispAtom::Int(1)), "1".to_string());
-        assert_eq!(gen_atom(&types::LispAtom::Float(1.1)), "1.1".to_string());
+        assert_eq!(gen_atom(&true.into()).unwrap(), "True".to_string());
+        assert_eq!(gen_atom(&().into()).unwrap(), "None".to_string());
         assert_eq!(
-            gen_atom(&types::LispAtom::String("foo".to_string())),
-            "\"foo\"".to_string()
+            gen_atom(&b"a\\d\x00"[..].into()).unwrap(),
+            "b\"a\\\\d\\x00\"".to_string()
+        );
+        assert_eq!(
+            gen_atom(&b"\\d+"[..].into()).unwrap(),
+            "br\"\\d+\"".to_string()
         );
     }
 
@@ -208,7 +1187,7 @@ mod tests {
         let c3 = arena.alloc("".into());
 
         let e1 = crate::alloc!(arena, [c1, c2, c3]);
-        assert_eq!(gen(&e1), "end=\"\"".to_string());
+        assert_eq!(gen(&e1).unwrap(), "end=\"\"".to_string());
     }
 
     #[test]
@@ -224,7 +1203,7 @@ mod tests {
         let e1 = crate::alloc!(arena, [c1, c2, c3]);
         let e2 = crate::alloc!(arena, [c4, c5, c6, e1]);
 
-        assert_eq!(gen(&e2), "print(line, end=\"\")".to_string());
+        assert_eq!(gen(&e2).unwrap(), "print(line, end=\"\")".to_string());
     }
 
     #[test]
@@ -247,7 +1226,7 @@ mod tests {
         let expect = "\
 for line in f:
     print(line, end=\"\")";
-        assert_eq!(gen(&e3), expect.to_string());
+        assert_eq!(gen(&e3).unwrap(), expect.to_string());
     }
 
     #[test]
@@ -275,7 +1254,7 @@ for line in f:
 with open(\"./temp\") as f:
     for line in f:
         print(line, end=\"\")";
-        assert_eq!(gen(&e4), expect.to_string());
+        assert_eq!(gen(&e4).unwrap(), expect.to_string());
     }
 
     #[test]
@@ -294,7 +1273,7 @@ with open(\"./temp\") as f:
         let expect = "\
 print(\"hello\")
 print(\"world\")";
-        assert_eq!(gen(&e1), expect.to_string());
+        assert_eq!(gen(&e1).unwrap(), expect.to_string());
     }
 
     #[test]
@@ -305,7 +1284,15 @@ print(\"world\")";
         let c3 = arena.alloc(types::LispAtom::new_symbol("bar").into());
 
         let e1 = crate::alloc!(arena, [c1, c2, c3]);
-        assert_eq!(gen(&e1), "foo.bar".to_string());
+        assert_eq!(gen(&e1).unwrap(), "foo.bar".to_string());
+
+        assert_eq!(gen_str("(attr 1 real)"), "(1).real");
+        assert_eq!(gen_str("(attr -1 real)"), "(-1).real");
+        assert_eq!(gen_str("(attr 1.5 real)"), "1.5.real");
+        assert_eq!(
+            gen_str("(attr 0x10000000000000000 real)"),
+            "(0x10000000000000000).real"
+        );
     }
 
     #[test]
@@ -319,6 +1306,627 @@ print(\"world\")";
         let e1 = crate::alloc!(arena, [c1, c2, c3]);
         let e2 = crate::alloc!(arena, [c4, e1]);
 
-        assert_eq!(gen(&e2), "foo.bar()".to_string());
+        assert_eq!(gen(&e2).unwrap(), "foo.bar()".to_string());
+    }
+
+    #[test]
+    fn test_gen_subscript() {
+        let mut arena = types::LispArena::default();
+        let c1 = arena.alloc(types::LispAtom::new_symbol("subscript").into());
+        let c2 = arena.alloc(types::LispAtom::new_symbol("F").into());
+        let c3 = arena.alloc(2.into());
+        let c4 = arena.alloc(types::LispAtom::new_symbol("i").into());
+
+        let e1 = crate::alloc!(arena, [c1, c2, c3]);
+        assert_eq!(gen(&e1).unwrap(), "F[2]".to_string());
+
+        let e2 = crate::alloc!(arena, [c1, c2, c3, c4]);
+        assert_eq!(gen(&e2).unwrap(), "F[2, i]".to_string());
+    }
+
+    #[test]
+    fn test_gen_with_nil() {
+        let mut arena = types::LispArena::default();
+        let c1 = arena.alloc(types::LispAtom::new_symbol("with").into());
+        let c2 = arena.alloc(types::LispAtom::new_raw_text("lock").into());
+        let c3 = arena.alloc(types::LispAtom::new_symbol("pass").into());
+        let nil = crate::alloc!(arena, []);
+
+        let e1 = crate::alloc!(arena, [c1, c2, nil, c3]);
+        let expect = "\
+with lock:
+    pass";
+        assert_eq!(gen(&e1).unwrap(), expect.to_string());
+        assert_eq!(gen_str("(with x nil (progn))"), expect.replace("lock", "x"));
+    }
+
+    #[test]
+    fn test_gen_list() {
+        let mut arena = types::LispArena::default();
+        let c1 = arena.alloc(types::LispAtom::new_symbol("list").into());
+        let c2 = arena.alloc(1.into());
+        let c3 = arena.alloc("a".into());
+
+        let e1 = crate::alloc!(arena, [c1]);
+        assert_eq!(gen(&e1).unwrap(), "[]".to_string());
+
+        let e2 = crate::alloc!(arena, [c1, c2, c3]);
+        assert_eq!(gen(&e2).unwrap(), "[1, \"a\"]".to_string());
+    }
+
+    #[test]
+    fn test_gen_error() {
+        let mut arena = types::LispArena::default();
+        let c1 = arena.alloc(types::LispAtom::new_symbol("for").into());
+        let c2 = arena.alloc(types::LispAtom::new_symbol("line").into());
+        let c3 = arena.alloc(types::LispAtom::new_symbol("f").into());
+        let c4 = arena.alloc(types::LispAtom::new_symbol("call").into());
+        let c5 = arena.alloc(types::LispAtom::new_symbol("kw").into());
+        let c6 = arena.alloc(types::LispAtom::new_symbol("foo").into());
+        let c7 = arena.alloc(1.into());
+
+        let e1 = crate::alloc!(arena, [c1, c2, c3, [c4, c6, [c5, c6]]]);
+        let err = gen(&e1).unwrap_err();
+        assert_eq!(
+            err,
+            GenError {
+                kind: GenErrorKind::WrongNumberOfArguments {
+                    name: "kw".to_string(),
+                    min: 2,
+                    max: Some(2),
+                    actual: 1,
+                },
+                path: vec![3, 2],
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "WrongNumberOfArguments: kw, expected: (2, 2), actual: 1 at [3, 2]"
+        );
+
+        let e2 = crate::alloc!(arena, [c4, [c6, c2]]);
+        let err = gen(&e2).unwrap_err();
+        assert_eq!(err.kind, GenErrorKind::UnknownForm("foo".to_string()));
+        assert_eq!(err.path, vec![1]);
+
+        let e3 = crate::alloc!(arena, [c7, c2]);
+        let err = gen(&e3).unwrap_err();
+        assert_eq!(err.to_string(), "WrongTypeArgument: symbol; 1 at [0]");
+
+        let e4 = crate::alloc!(arena, [c4, c6; c2]);
+        let err = gen(&e4).unwrap_err();
+        assert_eq!(err.to_string(), "WrongTypeArgument: list; line");
+
+        let e5 = {
+            let mut arena2 = types::LispArena::default();
+            let c8 = arena2.alloc(types::LispAtom::new_symbol("x").into());
+            crate::alloc!(arena, [c4, c6, c8])
+        };
+        let err = gen(&e5).unwrap_err();
+        assert_eq!(err.kind, GenErrorKind::DanglingReference);
+        assert_eq!(err.path, vec![2]);
+
+        let nil = crate::alloc!(arena, []);
+        let e6 = crate::alloc!(arena, [c4, c6, c7, nil]);
+        let err = gen(&e6).unwrap_err();
+        assert_eq!(err.to_string(), "WrongTypeArgument: expression; nil at [3]");
+    }
+
+    #[test]
+    fn test_gen_if() {
+        let expect = "\
+if x:
+    a
+    b";
+        assert_eq!(gen_str("(if x (progn a b))"), expect);
+
+        let expect = "\
+if x:
+    a
+else:
+    b
+    c";
+        assert_eq!(gen_str("(if x a b c)"), expect);
+
+        let expect = "\
+if x:
+    a
+elif y:
+    b
+elif z:
+    c
+else:
+    d";
+        assert_eq!(gen_str("(if x a (if y b (if z c d)))"), expect);
+
+        let expect = "\
+for line in f:
+    if x:
+        a
+    else:
+        pass";
+        assert_eq!(gen_str("(for line f (if x a (progn)))"), expect);
+    }
+
+    #[test]
+    fn test_gen_when_unless() {
+        assert_eq!(gen_str("(when x a b)"), "if x:\n    a\n    b");
+        assert_eq!(gen_str("(when x)"), "if x:\n    pass");
+        assert_eq!(gen_str("(unless x a)"), "if not x:\n    a");
+        assert_eq!(
+            gen_str("(unless (call (attr re search) \"a\" line) (call print line))"),
+            "if not re.search(\"a\", line):\n    print(line)"
+        );
+    }
+
+    #[test]
+    fn test_gen_ifexp() {
+        assert_eq!(gen_str("(ifexp c x y)"), "x if c else y");
+        assert_eq!(
+            gen_str("(assign z (ifexp c (call f x) y))"),
+            "z = f(x) if c else y"
+        );
+    }
+
+    #[test]
+    fn test_gen_while() {
+        let expect = "\
+while True:
+    line = f.readline()
+    if not line:
+        break";
+        assert_eq!(
+            gen_str(
+                "(while #true (progn (assign line (call (attr f readline))) (unless line (break))))"
+            ),
+            expect
+        );
+
+        let expect = "\
+while x:
+    pass
+else:
+    y";
+        assert_eq!(gen_str("(while x (pass) y)"), expect);
+    }
+
+    #[test]
+    fn test_gen_for_target() {
+        let expect = "\
+for i, line in enumerate(f):
+    if i:
+        continue
+    print(line)
+else:
+    pass";
+        assert_eq!(
+            gen_str("(for (i line) (call enumerate f) (progn (when i (continue)) (call print line)) (progn))"),
+            expect
+        );
+        assert_eq!(
+            gen_str("(for (i (a (* b))) xs (pass))"),
+            "for i, (a, *b) in xs:\n    pass"
+        );
+        assert_eq!(
+            gen_str("(for ((a b) c) xs (pass))"),
+            "for (a, b), c in xs:\n    pass"
+        );
+        assert_eq!(gen_str("(for (x) xs (pass))"), "for x, in xs:\n    pass");
+        assert_eq!(
+            gen_str("(for ((subscript d 0) (attr o x)) xs (pass))"),
+            "for d[0], o.x in xs:\n    pass"
+        );
+        assert_eq!(
+            gen_str("(for (tuple a (tuple b c)) xs (pass))"),
+            "for a, (b, c) in xs:\n    pass"
+        );
+        assert_eq!(
+            gen_str("(for (list a (list b)) xs (pass))"),
+            "for [a, [b]] in xs:\n    pass"
+        );
+        assert_eq!(
+            gen_str("(for (tuple a) xs (pass))"),
+            "for a, in xs:\n    pass"
+        );
+        assert_eq!(gen_str("(for (list) xs (pass))"), "for [] in xs:\n    pass");
+
+        let mut arena = types::LispArena::default();
+        let e = crate::reader::read("(for (a (call f x)) xs (pass))", &mut arena).unwrap();
+        assert_eq!(
+            gen(&e).unwrap_err().to_string(),
+            "WrongTypeArgument: assignment target; cons at [1, 1]"
+        );
+    }
+
+    #[test]
+    fn test_non_target_forms() {
+        // Every known form except attr, subscript, *, tuple and list is an
+        // expression; any other list destructures.
+        let mut arena = types::LispArena::default();
+        for name in ["call", "genexp", "if", "+", "**", "not-in", "compare"] {
+            let src = format!("(for ({} a b) xs (pass))", name);
+            let e = crate::reader::read(&src, &mut arena).unwrap();
+            assert_eq!(
+                gen(&e).unwrap_err().to_string(),
+                "WrongTypeArgument: assignment target; cons at [1]"
+            );
+        }
+        assert_eq!(
+            gen_str("(for (callback a) xs (pass))"),
+            "for callback, a in xs:\n    pass"
+        );
+    }
+
+    #[test]
+    fn test_gen_def() {
+        let expect = "\
+def f(a, b=1, *args, c, **kwargs):
+    return a";
+        assert_eq!(
+            gen_str("(def f (a (kw b 1) (* args) c (** kwargs)) (return a))"),
+            expect
+        );
+        assert_eq!(
+            gen_str("(def f (a * b) (pass))"),
+            "def f(a, *, b):\n    pass"
+        );
+        assert_eq!(gen_str("(def f ())"), "def f():\n    pass");
+
+        let expect = "\
+def g(it):
+    \"\"\"Yield from it.
+
+    Stops at None.\"\"\"
+    for x in it:
+        if is_none(x):
+            return
+        yield x
+    yield";
+        assert_eq!(
+            gen_str(
+                "(def g (it) \"Yield from it.\\n\\nStops at None.\"
+                   (for x it (progn (when (call is_none x) (return)) (yield x)))
+                   (yield))"
+            ),
+            expect
+        );
+    }
+
+    #[test]
+    fn test_gen_docstring() {
+        assert_eq!(
+            gen_str("(def f () \"say \\\"hi\\\"\")"),
+            "def f():\n    \"\"\"say \"hi\\\"\"\"\""
+        );
+        assert_eq!(
+            gen_str("(def f () \"a\\\\b\\x01\")"),
+            "def f():\n    \"\"\"a\\\\b\\x01\"\"\""
+        );
+    }
+
+    #[test]
+    fn test_gen_yield() {
+        assert_eq!(gen_str("(yield x)"), "yield x");
+        assert_eq!(gen_str("(assign y (yield x))"), "y = yield x");
+        assert_eq!(
+            gen_str("(call f (yield x) (kw k (yield)))"),
+            "f((yield x), k=(yield))"
+        );
+        assert_eq!(gen_str("(list (yield) (* xs))"), "[(yield), *xs]");
+        assert_eq!(gen_str("(dict k (yield))"), "{k: (yield)}");
+        assert_eq!(gen_str("(subscript d (yield))"), "d[(yield)]");
+        assert_eq!(gen_str("(+ 1 (yield x))"), "1 + (yield x)");
+    }
+
+    #[test]
+    fn test_gen_class() {
+        let expect = "\
+class Foo(Base, metaclass=Meta):
+    \"\"\"A foo.\"\"\"
+    def bar(self):
+        return 1";
+        assert_eq!(
+            gen_str(
+                "(class Foo (Base (kw metaclass Meta)) \"A foo.\" (def bar (self) (return 1)))"
+            ),
+            expect
+        );
+        assert_eq!(gen_str("(class Foo ())"), "class Foo:\n    pass");
+        assert_eq!(
+            gen_str("(class Foo () \"A foo.\")"),
+            "class Foo:\n    \"\"\"A foo.\"\"\""
+        );
+    }
+
+    #[test]
+    fn test_gen_decorate() {
+        let expect = "\
+@staticmethod
+@functools.cache
+def f():
+    return 1";
+        assert_eq!(
+            gen_str("(decorate staticmethod (attr functools cache) (def f () (return 1)))"),
+            expect
+        );
+    }
+
+    #[test]
+    fn test_gen_try() {
+        let expect = "\
+try:
+    x = int(line)
+except ValueError as e:
+    print(e)
+    continue
+except KeyError:
+    pass
+except:
+    raise
+else:
+    print(x)
+finally:
+    f.close()";
+        assert_eq!(
+            gen_str(
+                "(try (assign x (call int line))
+                   (except ValueError e (call print e) (continue))
+                   (except KeyError)
+                   (except nil nil (raise))
+                   (else (call print x))
+                   (finally (call (attr f close))))"
+            ),
+            expect
+        );
+
+        let mut arena = types::LispArena::default();
+        let e1 = crate::reader::read("(try x (except nil e))", &mut arena).unwrap();
+        assert_eq!(
+            gen(&e1).unwrap_err().to_string(),
+            "WrongTypeArgument: nil; e at [2, 2]"
+        );
+        let e2 = crate::reader::read("(try x (catch))", &mut arena).unwrap();
+        assert_eq!(
+            gen(&e2).unwrap_err().to_string(),
+            "WrongTypeArgument: except, else or finally clause; cons at [2]"
+        );
+        for (src, err) in [
+            (
+                "(try x (else y))",
+                "WrongTypeArgument: except or finally clause; cons at [2]",
+            ),
+            (
+                "(try x (finally y) (except nil nil z))",
+                "WrongTypeArgument: end of try; cons at [3]",
+            ),
+            (
+                "(try x (except nil nil y) (else z) (except nil nil w))",
+                "WrongTypeArgument: finally clause; cons at [4]",
+            ),
+            (
+                "(try x (except nil nil y) (else z) (else w))",
+                "WrongTypeArgument: finally clause; cons at [4]",
+            ),
+        ] {
+            let e = crate::reader::read(src, &mut arena).unwrap();
+            assert_eq!(gen(&e).unwrap_err().to_string(), err);
+        }
+        assert_eq!(
+            gen_str("(try x (finally y))"),
+            "try:\n    x\nfinally:\n    y"
+        );
+    }
+
+    #[test]
+    fn test_gen_raise_assert() {
+        assert_eq!(gen_str("(raise)"), "raise");
+        assert_eq!(
+            gen_str("(raise (call ValueError line))"),
+            "raise ValueError(line)"
+        );
+        assert_eq!(
+            gen_str("(raise (call ValueError line) e)"),
+            "raise ValueError(line) from e"
+        );
+        assert_eq!(gen_str("(assert x)"), "assert x");
+        assert_eq!(gen_str("(assert x \"no x\")"), "assert x, \"no x\"");
+    }
+
+    #[test]
+    fn test_gen_arithmetic() {
+        assert_eq!(gen_str("(+ a b c)"), "a + b + c");
+        assert_eq!(gen_str("(* (+ a b) c)"), "(a + b) * c");
+        assert_eq!(gen_str("(+ (* a b) c)"), "a * b + c");
+        assert_eq!(gen_str("(- (- a b) c)"), "a - b - c");
+        assert_eq!(gen_str("(- a (- b c))"), "a - (b - c)");
+        assert_eq!(gen_str("(// (% a 2) (@ b c))"), "a % 2 // (b @ c)");
+        assert_eq!(gen_str("(| (& a b) (^ c (<< d 1)))"), "a & b | c ^ d << 1");
+        assert_eq!(gen_str("(& (| a b) c)"), "(a | b) & c");
+        assert_eq!(gen_str("(** a (** b c))"), "a ** b ** c");
+        assert_eq!(gen_str("(** (** a b) c)"), "(a ** b) ** c");
+        assert_eq!(gen_str("(** (- a) (- b))"), "(-a) ** -b");
+        assert_eq!(gen_str("(** -1 2)"), "(-1) ** 2");
+        assert_eq!(gen_str("(- (** a 2))"), "-a ** 2");
+        assert_eq!(gen_str("(- (+ a b))"), "-(a + b)");
+        assert_eq!(gen_str("(~ (- a))"), "~-a");
+        assert_eq!(gen_str("(* a)"), "*a");
+        assert_eq!(gen_str("(call f (* (+ a b)) (** d))"), "f(*a + b, **d)");
+    }
+
+    #[test]
+    fn test_gen_comparison() {
+        assert_eq!(gen_str("(< a b c)"), "a < b < c");
+        assert_eq!(gen_str("(== (< a b) c)"), "(a < b) == c");
+        assert_eq!(gen_str("(not-in x (+ a b))"), "x not in a + b");
+        assert_eq!(gen_str("(is-not x #none)"), "x is not None");
+        assert_eq!(gen_str("(compare 0 <= i < n)"), "0 <= i < n");
+        assert_eq!(gen_str("(compare a in b is-not c)"), "a in b is not c");
+
+        let mut arena = types::LispArena::default();
+        let e1 = crate::reader::read("(compare a + b)", &mut arena).unwrap();
+        assert_eq!(
+            gen(&e1).unwrap_err().to_string(),
+            "WrongTypeArgument: comparison operator; + at [2]"
+        );
+        let e2 = crate::reader::read("(compare a < b <)", &mut arena).unwrap();
+        assert_eq!(
+            gen(&e2).unwrap_err().to_string(),
+            "WrongNumberOfArguments: compare, expected: (3, many), actual: 4"
+        );
+    }
+
+    #[test]
+    fn test_gen_boolean() {
+        assert_eq!(gen_str("(and a b c)"), "a and b and c");
+        assert_eq!(gen_str("(or (and a b) c)"), "a and b or c");
+        assert_eq!(gen_str("(and (or a b) c)"), "(a or b) and c");
+        assert_eq!(gen_str("(not (< a b))"), "not a < b");
+        assert_eq!(gen_str("(not (and a b))"), "not (a and b)");
+        assert_eq!(gen_str("(< (not a) b)"), "(not a) < b");
+        assert_eq!(
+            gen_str("(ifexp (or a b) (ifexp c x y) (ifexp d z w))"),
+            "(x if c else y) if a or b else z if d else w"
+        );
+        assert_eq!(gen_str("(attr (+ a b) real)"), "(a + b).real");
+        assert_eq!(gen_str("(call (lambda x x) 1)"), "(lambda x: x)(1)");
+        assert_eq!(gen_str("(subscript (ifexp c a b) 0)"), "(a if c else b)[0]");
+    }
+
+    #[test]
+    fn test_gen_tuple() {
+        assert_eq!(gen_str("(tuple)"), "()");
+        assert_eq!(gen_str("(tuple a)"), "(a,)");
+        assert_eq!(gen_str("(tuple a (* b) (+ c 1))"), "(a, *b, c + 1)");
+        assert_eq!(gen_str("(attr (tuple a) count)"), "(a,).count");
+    }
+
+    #[test]
+    fn test_gen_dict_set() {
+        assert_eq!(gen_str("(dict)"), "{}");
+        assert_eq!(
+            gen_str("(dict \"a\" 1 (** d) (lambda () 1) (lambda () 2))"),
+            "{\"a\": 1, **d, (lambda: 1): lambda: 2}"
+        );
+        assert_eq!(gen_str("(set)"), "set()");
+        assert_eq!(gen_str("(set a (* b))"), "{a, *b}");
+        assert_eq!(gen_str("(list (* a) (* b))"), "[*a, *b]");
+
+        let mut arena = types::LispArena::default();
+        let e1 = crate::reader::read("(dict a 1 b)", &mut arena).unwrap();
+        assert_eq!(
+            gen(&e1).unwrap_err().to_string(),
+            "WrongTypeArgument: key and value; b at [3]"
+        );
+    }
+
+    #[test]
+    fn test_gen_slice() {
+        assert_eq!(gen_str("(subscript x (slice))"), "x[:]");
+        assert_eq!(gen_str("(subscript x (slice 1))"), "x[1:]");
+        assert_eq!(gen_str("(subscript x (slice nil 2))"), "x[:2]");
+        assert_eq!(gen_str("(subscript x (slice nil nil -1))"), "x[::-1]");
+        assert_eq!(gen_str("(subscript x (slice a (+ b 1) 2))"), "x[a:b + 1:2]");
+        assert_eq!(gen_str("(subscript x (slice 1 nil) 0)"), "x[1:, 0]");
+        assert_eq!(gen_str("(subscript F -1)"), "F[-1]");
+    }
+
+    #[test]
+    fn test_gen_comprehension() {
+        assert_eq!(
+            gen_str("(listcomp (* x 2) (for x xs))"),
+            "[x * 2 for x in xs]"
+        );
+        assert_eq!(
+            gen_str(
+                "(setcomp (tuple i j) (for i a) (if (> i 0)) (for (j k) (call zip b c)) (if k))"
+            ),
+            "{(i, j) for i in a if i > 0 for j, k in zip(b, c) if k}"
+        );
+        assert_eq!(
+            gen_str("(dictcomp k (ifexp c v 0) (for (k v) (call (attr d items))))"),
+            "{k: v if c else 0 for k, v in d.items()}"
+        );
+        assert_eq!(
+            gen_str("(listcomp x (for x (ifexp c a b)) (if (lambda () 1)))"),
+            "[x for x in (a if c else b) if (lambda: 1)]"
+        );
+        assert_eq!(
+            gen_str("(call sum (genexp (call int x) (for x F)))"),
+            "sum(int(x) for x in F)"
+        );
+        assert_eq!(
+            gen_str("(call max (genexp x (for x xs)) (kw default 0))"),
+            "max((x for x in xs), default=0)"
+        );
+        assert_eq!(
+            gen_str(
+                "(call (attr \",\" join) (listcomp (call str x) (for x (listcomp y (for y ys)))))"
+            ),
+            "\",\".join([str(x) for x in [y for y in ys]])"
+        );
+
+        let mut arena = types::LispArena::default();
+        let e1 = crate::reader::read("(listcomp x (if x))", &mut arena).unwrap();
+        assert_eq!(
+            gen(&e1).unwrap_err().to_string(),
+            "WrongTypeArgument: for clause; cons at [2]"
+        );
+        let e2 = crate::reader::read("(listcomp x (for x xs) (if))", &mut arena).unwrap();
+        assert_eq!(
+            gen(&e2).unwrap_err().to_string(),
+            "WrongNumberOfArguments: if, expected: (1, 1), actual: 0 at [3]"
+        );
+    }
+
+    #[test]
+    fn test_gen_string() {
+        assert_eq!(gen_str("\"say \\\"hi\\\"\""), "'say \"hi\"'");
+        assert_eq!(gen_str("\"a\\nb\""), "\"a\\nb\"");
+        assert_eq!(gen_str("\"C:\\\\tmp\""), "r\"C:\\tmp\"");
+        assert_eq!(gen_str("\"\\\\\\n\""), "\"\\\\\\n\"");
+    }
+
+    #[test]
+    fn test_gen_constants() {
+        assert_eq!(gen_str("(list #true #false #none)"), "[True, False, None]");
+        assert_eq!(gen_str("(is-not x #none)"), "x is not None");
+        assert_eq!(gen_str("#b\"\\x00\\xff\""), "b\"\\x00\\xff\"");
+    }
+
+    #[test]
+    fn test_gen_fstring() {
+        assert_eq!(
+            gen_str("#f(\"{\" x \": \" (fmt (call f y) \">5\") \"}\")"),
+            "f\"{{{x}: {f(y):>5}}}\""
+        );
+        assert_eq!(
+            gen_str("#f((subscript d \"k\") (lambda () 1) (dict a 1) (+ a b))"),
+            "f'{d[\"k\"]}{(lambda: 1)}{({a: 1})}{a + b}'"
+        );
+        assert_eq!(
+            gen_str("#f((call (attr \"\\t\" join) F) \"!\")"),
+            "\"{}!\".format(\"\\t\".join(F))"
+        );
+    }
+
+    #[test]
+    fn test_gen_number() {
+        assert_eq!(
+            gen_str("(list 0x1f 1e100 1e-7 -0.0 2.5)"),
+            "[31, 1e100, 1e-7, -0.0, 2.5]"
+        );
+        assert_eq!(
+            gen_str("(+ 18446744073709551616 -0xffffffffffffffffff)"),
+            "18446744073709551616 + -0xffffffffffffffffff"
+        );
+        assert_eq!(
+            gen_str("(** -0xffffffffffffffffff 2)"),
+            "(-0xffffffffffffffffff) ** 2"
+        );
+        assert_eq!(
+            gen_str("(list #inf #-inf #nan)"),
+            "[float(\"inf\"), float(\"-inf\"), float(\"nan\")]"
+        );
+        assert_eq!(gen_str("(** #-inf 2)"), "float(\"-inf\") ** 2");
     }
 }
@@ -4,4 +4,6 @@
 
 pub mod gen_python;
 pub mod generator;
+pub mod python_str;
+pub mod reader;
 pub mod types;
@@ -5,9 +5,63 @@ use pype::{gen_python, generator, types};
 use std::{
     fs,
     io::{self, Write},
+    os::unix::process::ExitStatusExt,
     process,
 };
 
+/// Short options that require a value, so the rest of a bundle (or the next
+/// argument) is their value rather than more flags.
+const SHORT_WITH_VALUE: &str = "evLBEFOm";
+
+/// Long options that require a value, so the next argument is their value
+/// unless it is given as `--opt=value`.
+const LONG_WITH_VALUE: &[&str] = &[
+    "python",
+    "python-arg",
+    "var",
+    "lisp",
+    "lisp-file",
+    "begin",
+    "end",
+    "ors",
+    "ofs",
+    "width",
+    "module",
+];
+
+/// Rewrite a bare `-i` or `-0`, also at the end of a bundle like `-pi`, to
+/// its long form so that it does not take the next argument (a file name) as
+/// its value: `-pi` becomes `-p --in-place`.  Also tells whether the next
+/// argument is the value of the last option.
+fn split_bare_maybe(arg: String) -> (Vec<String>, bool) {
+    if let Some(name) = arg.strip_prefix("--") {
+        let takes_value = LONG_WITH_VALUE.contains(&name);
+        return (vec![arg], takes_value);
+    }
+    let Some(flags) = arg.strip_prefix('-') else {
+        return (vec![arg], false);
+    };
+    for (i, c) in flags.char_indices() {
+        let last = i + 1 == flags.len();
+        let long = match c {
+            c if SHORT_WITH_VALUE.contains(c) => return (vec![arg.clone()], last),
+            'i' => "--in-place",
+            '0' => "--record-separator",
+            _ => continue,
+        };
+        // Otherwise the rest of the bundle is the value, as in `-i.bak`.
+        if last {
+            let mut x = vec![long.to_string()];
+            if i > 0 {
+                x.insert(0, format!("-{}", &flags[..i]));
+            }
+            return (x, false);
+        }
+        break;
+    }
+    (vec![arg], false)
+}
+
 fn argparse() -> (getopts::Options, getopts::Matches) {
     let mut opts = getopts::Options::new();
 
@@ -20,13 +74,37 @@ fn argparse() -> (getopts::Options, getopts::Matches) {
         getopts::Occur::Optional,
     );
     opts.opt(
-        "v",
+        "V",
         "version",
         "print the version",
         "",
         getopts::HasArg::No,
         getopts::Occur::Optional,
     );
+    opts.opt(
+        "r",
+        "run",
+        "run the program with python instead of printing it",
+        "",
+        getopts::HasArg::No,
+        getopts::Occur::Optional,
+    );
+    opts.opt(
+        "",
+        "python",
+        "python interpreter to run (default: $PYPE_PYTHON or python; implies -r)",
+        "path",
+        getopts::HasArg::Yes,
+        getopts::Occur::Optional,
+    );
+    opts.opt(
+        "",
+        "python-arg",
+        "extra argument for the python interpreter (implies -r)",
+        "arg",
+        getopts::HasArg::Yes,
+        getopts::Occur::Multi,
+    );
     opts.opt(
         "e",
         "",
@@ -35,6 +113,46 @@ fn argparse() -> (getopts::Options, getopts::Matches) {
         getopts::HasArg::Yes,
         getopts::Occur::Multi,
     );
+    opts.opt(
+        "v",
+        "var",
+        "define a variable before the program runs (type: str, int, float, bool, json)",
+        "name[:type]=value",
+        getopts::HasArg::Yes,
+        getopts::Occur::Multi,
+    );
+    opts.opt(
+        "L",
+        "lisp",
+        "program written in pype's Lisp IR (instead of -e)",
+        "sexp",
+        getopts::HasArg::Yes,
+        getopts::Occur::Multi,
+    );
+    opts.opt(
+        "",
+        "lisp-file",
+        "read the Lisp IR program from a file (instead of -e)",
+        "path",
+        getopts::HasArg::Yes,
+        getopts::Occur::Multi,
+    );
+    opts.opt(
+        "B",
+        "begin",
+        "code to run before the line loop",
+        "command",
+        getopts::HasArg::Yes,
+        getopts::Occur::Multi,
+    );
+    opts.opt(
+        "E",
+        "end",
+        "code to run after the line loop",
+        "command",
+        getopts::HasArg::Yes,
+        getopts::Occur::Multi,
+    );
     opts.opt(
         "n",
         "",
@@ -43,6 +161,46 @@ fn argparse() -> (getopts::Options, getopts::Matches) {
         getopts::HasArg::No,
         getopts::Occur::Optional,
     );
+    opts.opt(
+        "p",
+        "",
+        "iterate over lines and print them",
+        "",
+        getopts::HasArg::No,
+        getopts::Occur::Optional,
+    );
+    opts.opt(
+        "0",
+        "record-separator",
+        "input record separator as octal (none: NUL, 0: paragraphs, 777: whole input)",
+        "OCTAL",
+        getopts::HasArg::Maybe,
+        getopts::Occur::Optional,
+    );
+    opts.opt(
+        "z",
+        "null-data",
+        "input records are separated by NUL",
+        "",
+        getopts::HasArg::No,
+        getopts::Occur::Optional,
+    );
+    opts.opt(
+        "a",
+        "",
+        "autosplit each line into F",
+        "",
+        getopts::HasArg::No,
+        getopts::Occur::Optional,
+    );
+    opts.opt(
+        "F",
+        "",
+        "field separator for -a (string or regex)",
+        "sep",
+        getopts::HasArg::Yes,
+        getopts::Occur::Optional,
+    );
     opts.opt(
         "l",
         "",
@@ -51,6 +209,78 @@ fn argparse() -> (getopts::Options, getopts::Matches) {
         getopts::HasArg::No,
         getopts::Occur::Optional,
     );
+    opts.opt(
+        "i",
+        "in-place",
+        "edit files in place (makes backup if SUFFIX supplied)",
+        "SUFFIX",
+        getopts::HasArg::Maybe,
+        getopts::Occur::Optional,
+    );
+    opts.opt(
+        "",
+        "skip-errors",
+        "report records that raise an exception to stderr and continue",
+        "",
+        getopts::HasArg::No,
+        getopts::Occur::Optional,
+    );
+    opts.opt(
+        "O",
+        "ors",
+        "output record separator added by print (escapes like \\0 and \\r\\n allowed)",
+        "sep",
+        getopts::HasArg::Yes,
+        getopts::Occur::Optional,
+    );
+    opts.opt(
+        "",
+        "ofs",
+        "output field separator between print arguments",
+        "sep",
+        getopts::HasArg::Yes,
+        getopts::Occur::Optional,
+    );
+    opts.opt(
+        "",
+        "dump-ir",
+        "print the Lisp IR of the program instead of the program",
+        "",
+        getopts::HasArg::No,
+        getopts::Occur::Optional,
+    );
+    opts.opt(
+        "",
+        "dump-steps",
+        "print the Lisp IR after each transform (implies --dump-ir)",
+        "",
+        getopts::HasArg::No,
+        getopts::Occur::Optional,
+    );
+    opts.opt(
+        "",
+        "pretty",
+        "pretty-print the dumped Lisp IR",
+        "",
+        getopts::HasArg::No,
+        getopts::Occur::Optional,
+    );
+    opts.opt(
+        "",
+        "width",
+        "line width for --pretty (default 80)",
+        "N",
+        getopts::HasArg::Yes,
+        getopts::Occur::Optional,
+    );
+    opts.opt(
+        "",
+        "dump-python",
+        "print the generated program only, without reading stdin",
+        "",
+        getopts::HasArg::No,
+        getopts::Occur::Optional,
+    );
     opts.opt(
         "m",
         "module",
@@ -60,7 +290,20 @@ fn argparse() -> (getopts::Options, getopts::Matches) {
         getopts::Occur::Multi,
     );
 
-    let args = match opts.parse(std::env::args().skip(1)) {
+    let mut end_of_opts = false;
+    let mut value_next = false;
+    let argv = std::env::args().skip(1).flat_map(|x| {
+        end_of_opts |= x == "--";
+        if end_of_opts || std::mem::take(&mut value_next) {
+            return vec![x];
+        }
+        let (x, takes_value) = split_bare_maybe(x);
+        value_next = takes_value;
+        x
+    });
+    let argv = argv.collect::<Vec<_>>();
+
+    let args = match opts.parse(argv) {
         Ok(args) => args,
         Err(err) => {
             eprintln!("{}", err);
@@ -80,14 +323,141 @@ fn argparse() -> (getopts::Options, getopts::Matches) {
         process::exit(0);
     }
 
+    if args.opt_present("i") && args.free.is_empty() {
+        eprintln!("-i requires at least one file argument");
+        eprint!("{}", opts.usage("Usage: pype [options]"));
+        process::exit(1);
+    }
+
+    if args.opt_present("i") && args.free.iter().any(|x| x == "-") {
+        eprintln!("-i cannot edit stdin in place");
+        process::exit(1);
+    }
+
+    if args.opt_present("e") && is_lisp(&args) {
+        eprintln!("-e cannot be used together with -L or --lisp-file");
+        process::exit(1);
+    }
+
+    if let Some(width) = args.opt_str("width") {
+        if !matches!(width.parse::<usize>(), Ok(x) if x > 0) {
+            eprintln!("invalid --width: {}", width);
+            process::exit(1);
+        }
+    }
+
+    if args.opt_str("F").as_deref() == Some("") {
+        eprintln!("-F requires a non-empty separator");
+        process::exit(1);
+    }
+
+    if args.opt_present("skip-errors") && !gen_python::is_loop(&args) {
+        eprintln!("--skip-errors requires -n, -p, -a or -F");
+        process::exit(1);
+    }
+
+    if let Err(err) = gen_python::record_separator(&args) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+
+    for spec in args.opt_strs("v") {
+        if let Err(err) = gen_python::variable(&spec) {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    }
+
     (opts, args)
 }
 
+fn is_dump(args: &getopts::Matches) -> bool {
+    ["dump-ir", "dump-steps", "dump-python"]
+        .iter()
+        .any(|x| args.opt_present(x))
+}
+
+fn dump_ir(e: &types::LispExpRef, args: &getopts::Matches) {
+    let e_ptr = e.upgrade().unwrap();
+    let e_ref = e_ptr.borrow();
+    match args.opt_present("pretty") {
+        true => println!("{}", e_ref.to_pretty_string(pretty_width(args))),
+        false => println!("{}", e_ref),
+    }
+}
+
+/// Line width for --pretty, validated in argparse.
+fn pretty_width(args: &getopts::Matches) -> usize {
+    match args.opt_str("width") {
+        Some(width) => width.parse().unwrap(),
+        None => 80,
+    }
+}
+
+/// With --dump-steps, print the IR after the transform `name`.
+fn dump_step(name: &str, e: types::LispExpRef, args: &getopts::Matches) -> types::LispExpRef {
+    if args.opt_present("dump-steps") {
+        println!(";; {}", name);
+        dump_ir(&e, args);
+    }
+    e
+}
+
+fn is_lisp(args: &getopts::Matches) -> bool {
+    args.opt_present("lisp") || args.opt_present("lisp-file")
+}
+
+/// Run `program` (or stdin, if None) with python and exit with its status.
+fn run_python(program: Option<&str>, args: &getopts::Matches) -> ! {
+    let python = args
+        .opt_str("python")
+        .or_else(|| std::env::var("PYPE_PYTHON").ok().filter(|x| !x.is_empty()))
+        .unwrap_or_else(|| "python".to_string());
+
+    let mut cmd = process::Command::new(&python);
+    cmd.args(args.opt_strs("python-arg"));
+    match program {
+        Some(program) => cmd.arg("-c").arg(program),
+        None => cmd.arg("-"),
+    };
+
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            eprintln!("pype: python interpreter not found: {}", python);
+            process::exit(127);
+        }
+        Err(err) => {
+            eprintln!("pype: failed to run {}: {}", python, err);
+            process::exit(126);
+        }
+    };
+
+    // C-c is delivered to python as well; let it decide how to exit.
+    unsafe {
+        let _ = nix::sys::signal::signal(
+            nix::sys::signal::Signal::SIGINT,
+            nix::sys::signal::SigHandler::SigIgn,
+        );
+    }
+
+    let status = child.wait().unwrap();
+    match (status.code(), status.signal()) {
+        (Some(code), _) => process::exit(code),
+        (None, Some(signal)) => process::exit(128 + signal),
+        (None, None) => process::exit(1),
+    }
+}
+
 fn main() {
     let (opts, args) = argparse();
 
     // no -e: stdin is python code.  Just copy it to stdout.
-    if !args.opt_present("e") {
+    if !args.opt_present("e") && !is_lisp(&args) {
+        if gen_python::is_run(&args) {
+            run_python(None, &args);
+        }
+
         let r = io::stdin();
         let w = io::stdout();
         let mut r = r.lock();
@@ -96,31 +466,93 @@ fn main() {
         return;
     }
 
-    let tmp_dir = tempfile::tempdir().unwrap();
+    // Only created when needed: run_python exits without dropping it.
+    let use_fifo = gen_python::uses_fifo(&args);
+    let tmp_dir = use_fifo.then(|| tempfile::tempdir().unwrap());
     let pid = process::id();
-    let fifo_path = tmp_dir.path().join(format!("pype__{pid}.fifo"));
-    nix::unistd::mkfifo(&fifo_path, nix::sys::stat::Mode::S_IRWXU).unwrap();
-
-    let fifo_path_str = fifo_path.to_str().unwrap();
+    let fifo_path = tmp_dir
+        .as_ref()
+        .map(|x| x.path().join(format!("pype__{pid}.fifo")));
+    if let Some(fifo_path) = fifo_path.as_ref().filter(|_| !is_dump(&args)) {
+        nix::unistd::mkfifo(fifo_path, nix::sys::stat::Mode::S_IRWXU).unwrap();
+    }
 
     let mut arena = types::LispArena::default();
-    let e = gen_python::do_e(&opts, &args, &mut arena);
+    let e = match is_lisp(&args) {
+        true => match gen_python::do_lisp(&opts, &args, &mut arena) {
+            Ok(e) => dump_step("do_lisp", e, &args),
+            Err(err) => {
+                eprintln!("pype: {}", err);
+                process::exit(1);
+            }
+        },
+        false => dump_step("do_e", gen_python::do_e(&opts, &args, &mut arena), &args),
+    };
+    let e = gen_python::do_a(e, &opts, &args, &mut arena);
+    let e = dump_step("do_a", e, &args);
     let e = gen_python::do_l(e, &opts, &args, &mut arena);
+    let e = dump_step("do_l", e, &args);
+    let e = gen_python::do_p(e, &opts, &args, &mut arena);
+    let e = dump_step("do_p", e, &args);
+    let e = gen_python::do_skip_errors(e, &opts, &args, &mut arena);
+    let e = dump_step("do_skip_errors", e, &args);
     let e = gen_python::do_n(e, &opts, &args, &mut arena);
+    let e = dump_step("do_n", e, &args);
+    let e = gen_python::do_inpt(
+        e,
+        fifo_path.as_ref().map(|x| x.to_str().unwrap()),
+        &opts,
+        &args,
+        &mut arena,
+    );
+    let e = dump_step("do_inpt", e, &args);
+    let e = gen_python::do_begin_end(e, &opts, &args, &mut arena);
+    let e = dump_step("do_begin_end", e, &args);
+    let e = gen_python::do_v(e, &opts, &args, &mut arena);
+    let e = dump_step("do_v", e, &args);
     let e = gen_python::do_l_post(e, &opts, &args, &mut arena);
-    let e = gen_python::do_inpt(e, fifo_path_str, &opts, &args, &mut arena);
+    let e = dump_step("do_l_post", e, &args);
     let e = gen_python::do_m(e, &opts, &args, &mut arena);
+    let e = dump_step("do_m", e, &args);
+
+    if args.opt_present("dump-steps") {
+        return;
+    }
+
+    if args.opt_present("dump-ir") {
+        dump_ir(&e, &args);
+        return;
+    }
+
+    let code = match generator::gen(&e) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("pype: {}", err);
+            process::exit(1);
+        }
+    };
+
+    if args.opt_present("dump-python") {
+        println!("{}", code);
+        return;
+    }
+
+    if gen_python::is_run(&args) {
+        run_python(Some(&code), &args);
+    }
 
-    // let e_ptr = e.upgrade().unwrap();
-    // println!("{}", e_ptr.borrow().to_string());
-    println!("{}", generator::gen(&e));
+    println!("{}", code);
 
     io::stdout().flush().unwrap();
     nix::unistd::close(1).unwrap();
 
+    let Some(fifo_path) = fifo_path else {
+        return;
+    };
+
     let r = io::stdin();
     let mut reader = r.lock();
-    let mut w = fs::File::create(&fifo_path).unwrap();
+    let mut w = fs::File::create(fifo_path).unwrap();
 
     // TODO: receive C-c, cleanup tempdir
     _ = io::copy(&mut reader, &mut w);
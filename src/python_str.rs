@@ -0,0 +1,360 @@
+//! Python string and bytes literals.
+
+/// Quote to delimit `s` with: double quotes unless only they need escaping.
+fn choose_quote(s: &str) -> char {
+    match s.contains('"') && !s.contains('\'') {
+        true => '\'',
+        false => '"',
+    }
+}
+
+/// Escape sequence for `c` in a non-raw literal, if it needs one.
+fn escape(c: char, quote: char) -> Option<String> {
+    let x = match c {
+        '\\' => "\\\\".to_string(),
+        '\n' => "\\n".to_string(),
+        '\r' => "\\r".to_string(),
+        '\t' => "\\t".to_string(),
+        c if c == quote => format!("\\{}", c),
+        c if c.is_control() && (c as u32) <= 0xff => format!("\\x{:02x}", c as u32),
+        c if c.is_control() => format!("\\u{:04x}", c as u32),
+        _ => return None,
+    };
+    Some(x)
+}
+
+/// Body of a raw literal for `s`, if `s` can be written as one: it must not
+/// contain `quote` or control characters, nor end in an odd number of
+/// backslashes.
+fn raw_body(s: &str, quote: char) -> Option<&str> {
+    let trailing = s.chars().rev().take_while(|c| *c == '\\').count();
+    match s.chars().any(|c| c == quote || c.is_control()) || trailing % 2 == 1 {
+        true => None,
+        false => Some(s),
+    }
+}
+
+/// Python literal for the string `s`.
+///
+/// With `raw`, an `r"..."` literal is produced when `s` can be written as
+/// one, which reads better for regexes and Windows paths; otherwise the
+/// literal is escaped as usual.
+///
+/// ```
+/// use pype::python_str::str_literal;
+///
+/// assert_eq!(str_literal("a\"b\n", false), r#"'a"b\n'"#);
+/// assert_eq!(str_literal(r"C:\tmp", true), r#"r"C:\tmp""#);
+/// assert_eq!(str_literal("a\\", true), r#""a\\""#);
+/// ```
+pub fn str_literal(s: &str, raw: bool) -> String {
+    let quote = choose_quote(s);
+    if let Some(body) = raw.then(|| raw_body(s, quote)).flatten() {
+        return format!("r{}{}{}", quote, body, quote);
+    }
+
+    let mut x = String::new();
+    x.push(quote);
+    for c in s.chars() {
+        match escape(c, quote) {
+            Some(e) => x.push_str(&e),
+            None => x.push(c),
+        }
+    }
+    x.push(quote);
+    x
+}
+
+/// Python literal for the bytes `b`, `b"..."` (or `br"..."` with `raw` when
+/// possible).  Bytes outside printable ASCII are written as `\xHH`.
+///
+/// ```
+/// use pype::python_str::bytes_literal;
+///
+/// assert_eq!(bytes_literal(b"a\x00\xff'", false), r#"b"a\x00\xff'""#);
+/// assert_eq!(bytes_literal(br"\d+", true), r#"br"\d+""#);
+/// ```
+pub fn bytes_literal(b: &[u8], raw: bool) -> String {
+    if raw && b.iter().all(|c| c.is_ascii() && !c.is_ascii_control()) {
+        let s = std::str::from_utf8(b).unwrap();
+        let quote = choose_quote(s);
+        if let Some(body) = raw_body(s, quote) {
+            return format!("br{}{}{}", quote, body, quote);
+        }
+    }
+
+    let s = b.iter().map(|c| *c as char).collect::<String>();
+    let quote = choose_quote(&s);
+    let mut x = format!("b{}", quote);
+    for c in s.chars() {
+        match (escape(c, quote), c.is_ascii()) {
+            (Some(e), _) => x.push_str(&e),
+            (None, true) => x.push(c),
+            (None, false) => x.push_str(&format!("\\x{:02x}", c as u32)),
+        }
+    }
+    x.push(quote);
+    x
+}
+
+/// Triple-quoted literal for the docstring `s`.  Newlines stay literal so
+/// the docstring reads as written; a quote is escaped when it could close
+/// the literal, that is when another quote or the end of `s` follows.
+///
+/// ```
+/// use pype::python_str::docstring_literal;
+///
+/// assert_eq!(docstring_literal("say \"hi\""), r#""""say "hi\"""""#);
+/// assert_eq!(docstring_literal("a\n\tb"), "\"\"\"a\n\\tb\"\"\"");
+/// ```
+pub fn docstring_literal(s: &str) -> String {
+    let mut x = String::from("\"\"\"");
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => x.push(c),
+            '"' if matches!(chars.peek(), None | Some('"')) => x.push_str("\\\""),
+            '"' => x.push(c),
+            c => match escape(c, '"') {
+                Some(e) => x.push_str(&e),
+                None => x.push(c),
+            },
+        }
+    }
+    x.push_str("\"\"\"");
+    x
+}
+
+/// Field of an f-string: literal text, or the code of an interpolated
+/// expression with an optional format spec.
+pub enum FStringField<'a> {
+    Text(&'a str),
+    Exp(&'a str, Option<&'a str>),
+}
+
+/// Python f-string literal for `fields`.
+///
+/// Before Python 3.12 an interpolated expression may not contain a
+/// backslash or the literal's own quote, so when no quote works the fields
+/// are formatted with `str.format` instead.
+///
+/// ```
+/// use pype::python_str::{fstring_literal, FStringField};
+///
+/// let fields = [
+///     FStringField::Text("{n} = "),
+///     FStringField::Exp("d[\"n\"]", Some(".2f")),
+/// ];
+/// assert_eq!(fstring_literal(&fields), r#"f'{{n}} = {d["n"]:.2f}'"#);
+/// ```
+pub fn fstring_literal(fields: &[FStringField]) -> String {
+    let code = fields
+        .iter()
+        .flat_map(|x| match x {
+            FStringField::Text(_) => vec![],
+            FStringField::Exp(exp, spec) => vec![*exp, spec.unwrap_or("")],
+        })
+        .collect::<String>();
+    let quote = match (code.contains('"'), code.contains('\'')) {
+        _ if code.contains(['\\', '\n', '#']) => None,
+        (false, _) => Some('"'),
+        (true, false) => Some('\''),
+        (true, true) => None,
+    };
+
+    let Some(quote) = quote else {
+        let template = fields
+            .iter()
+            .map(|x| match x {
+                FStringField::Text(t) => t.replace('{', "{{").replace('}', "}}"),
+                FStringField::Exp(_, None) => "{}".to_string(),
+                FStringField::Exp(_, Some(spec)) => format!("{{:{}}}", spec),
+            })
+            .collect::<String>();
+        let args = fields
+            .iter()
+            .filter_map(|x| match x {
+                FStringField::Text(_) => None,
+                FStringField::Exp(exp, _) => Some(*exp),
+            })
+            .collect::<Vec<_>>();
+        return format!(
+            "{}.format({})",
+            str_literal(&template, false),
+            args.join(", ")
+        );
+    };
+
+    let mut x = format!("f{}", quote);
+    for field in fields {
+        match field {
+            FStringField::Text(t) => {
+                for c in t.chars() {
+                    match (escape(c, quote), c) {
+                        (Some(e), _) => x.push_str(&e),
+                        (None, '{' | '}') => {
+                            x.push(c);
+                            x.push(c);
+                        }
+                        (None, _) => x.push(c),
+                    }
+                }
+            }
+            FStringField::Exp(exp, None) => x.push_str(&format!("{{{}}}", exp)),
+            FStringField::Exp(exp, Some(spec)) => x.push_str(&format!("{{{}:{}}}", exp, spec)),
+        }
+    }
+    x.push(quote);
+    x
+}
+
+/// Decode backslash escapes as Python does in a string literal; unknown
+/// escapes are kept as they are.
+///
+/// ```
+/// use pype::python_str::unescape;
+///
+/// assert_eq!(unescape(r"\r\n"), "\r\n");
+/// assert_eq!(unescape(r"\x00|\0|\101|\q"), "\0|\0|A|\\q");
+/// ```
+pub fn unescape(s: &str) -> String {
+    let mut x = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            x.push(c);
+            continue;
+        }
+        let Some(e) = chars.next() else {
+            x.push(c);
+            break;
+        };
+        let decoded = match e {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            'a' => '\x07',
+            'b' => '\x08',
+            'f' => '\x0c',
+            'v' => '\x0b',
+            '\\' | '\'' | '"' => e,
+            'x' => {
+                let hex = chars.as_str().get(..2).unwrap_or("");
+                match u8::from_str_radix(hex, 16) {
+                    Ok(code) if hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+                        chars.nth(1);
+                        char::from(code)
+                    }
+                    _ => {
+                        x.push_str("\\x");
+                        continue;
+                    }
+                }
+            }
+            '0'..='7' => {
+                // Up to three octal digits, as in "\0" or "\012".
+                let mut code = e.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match chars.clone().next().and_then(|c| c.to_digit(8)) {
+                        Some(digit) => code = code * 8 + digit,
+                        None => break,
+                    }
+                    chars.next();
+                }
+                char::from_u32(code).unwrap()
+            }
+            _ => {
+                x.push(c);
+                x.push(e);
+                continue;
+            }
+        };
+        x.push(decoded);
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_str_literal() {
+        assert_eq!(str_literal("abc", false), "\"abc\"");
+        assert_eq!(str_literal("it's", false), "\"it's\"");
+        assert_eq!(str_literal("say \"hi\"", false), "'say \"hi\"'");
+        assert_eq!(str_literal("'\"", false), "\"'\\\"\"");
+        assert_eq!(
+            str_literal("a\tb\r\n\0\x7f", false),
+            "\"a\\tb\\r\\n\\x00\\x7f\""
+        );
+        assert_eq!(str_literal("\u{85}\u{2028}é", false), "\"\\x85\u{2028}é\"");
+        assert_eq!(str_literal("\u{9f}", false), "\"\\x9f\"");
+        assert_eq!(str_literal("C:\\Users\\me", false), "\"C:\\\\Users\\\\me\"");
+    }
+
+    #[test]
+    fn test_str_literal_raw() {
+        assert_eq!(str_literal("\\s+", true), "r\"\\s+\"");
+        assert_eq!(str_literal("\\\\", true), "r\"\\\\\"");
+        assert_eq!(str_literal("\\", true), "\"\\\\\"");
+        assert_eq!(str_literal("a\\\nb", true), "\"a\\\\\\nb\"");
+        assert_eq!(str_literal("'\\d\"", true), "\"'\\\\d\\\"\"");
+        assert_eq!(str_literal("\"\\d\"", true), "r'\"\\d\"'");
+    }
+
+    #[test]
+    fn test_bytes_literal() {
+        assert_eq!(bytes_literal(b"", false), "b\"\"");
+        assert_eq!(bytes_literal("é".as_bytes(), false), "b\"\\xc3\\xa9\"");
+        assert_eq!(bytes_literal(b"\"\\", false), "b'\"\\\\'");
+        assert_eq!(bytes_literal(b"\\\n", true), "b\"\\\\\\n\"");
+    }
+
+    #[test]
+    fn test_docstring_literal() {
+        assert_eq!(docstring_literal(""), "\"\"\"\"\"\"");
+        assert_eq!(docstring_literal("\"\"\""), "\"\"\"\\\"\\\"\\\"\"\"\"");
+        assert_eq!(docstring_literal("a\"\"b"), "\"\"\"a\\\"\"b\"\"\"");
+        assert_eq!(docstring_literal("C:\\\0"), "\"\"\"C:\\\\\\x00\"\"\"");
+    }
+
+    #[test]
+    fn test_fstring_literal() {
+        use FStringField::*;
+
+        assert_eq!(fstring_literal(&[]), "f\"\"");
+        assert_eq!(
+            fstring_literal(&[Text("a\"b\n"), Exp("x", None), Text("}")]),
+            "f\"a\\\"b\\n{x}}}\""
+        );
+        assert_eq!(
+            fstring_literal(&[Exp("d['k']", None), Text("'")]),
+            "f\"{d['k']}'\""
+        );
+        assert_eq!(
+            fstring_literal(&[
+                Text("{"),
+                Exp("x", Some(">5")),
+                Exp("\"\\t\".join(y)", None)
+            ]),
+            "\"{{{:>5}{}\".format(x, \"\\t\".join(y))"
+        );
+        assert_eq!(
+            fstring_literal(&[Exp("'a'", None), Exp("\"b\"", None)]),
+            "\"{}{}\".format('a', \"b\")"
+        );
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape("a\\tb"), "a\tb");
+        assert_eq!(unescape("\\\\n"), "\\n");
+        assert_eq!(unescape("\\x4g"), "\\x4g");
+        assert_eq!(unescape("\\x41\\x42"), "AB");
+        assert_eq!(unescape("end\\"), "end\\");
+        assert_eq!(unescape("\\0"), "\0");
+        assert_eq!(unescape("\\012"), "\n");
+        assert_eq!(unescape("\\101\\1018"), "AA8");
+    }
+}
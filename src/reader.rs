@@ -0,0 +1,579 @@
+use crate::types;
+
+use std::{fmt, iter::Peekable, str::Chars};
+
+#[derive(Debug, PartialEq)]
+pub enum ReadErrorKind {
+    UnexpectedEof,
+    UnexpectedChar(char),
+    UnterminatedString,
+    InvalidEscape(char),
+    InvalidDot,
+    InvalidHash(String),
+    InvalidByte(char),
+}
+
+/// Error while reading, with the 1-based position it was detected at.
+#[derive(Debug, PartialEq)]
+pub struct ReadError {
+    pub kind: ReadErrorKind,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: ", self.line, self.column)?;
+        match &self.kind {
+            ReadErrorKind::UnexpectedEof => write!(f, "unexpected end of input"),
+            ReadErrorKind::UnexpectedChar(c) => write!(f, "unexpected character: {:?}", c),
+            ReadErrorKind::UnterminatedString => write!(f, "unterminated string"),
+            ReadErrorKind::InvalidEscape(c) => write!(f, "invalid escape sequence: \\{}", c),
+            ReadErrorKind::InvalidDot => write!(f, "invalid dotted list"),
+            ReadErrorKind::InvalidHash(token) => write!(f, "invalid # syntax: #{}", token),
+            ReadErrorKind::InvalidByte(c) => {
+                write!(f, "character outside a byte in bytes literal: {:?}", c)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReadError {}
+
+struct Reader<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || "()\"';".contains(c)
+}
+
+impl<'a> Reader<'a> {
+    fn new(src: &'a str) -> Self {
+        Reader {
+            chars: src.chars().peekable(),
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn error(&self, kind: ReadErrorKind) -> ReadError {
+        ReadError {
+            kind,
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        match c {
+            '\n' => {
+                self.line += 1;
+                self.column = 1;
+            }
+            _ => self.column += 1,
+        }
+        Some(c)
+    }
+
+    /// Skip whitespace and comments, returning the next significant char.
+    fn skip_blank(&mut self) -> Option<char> {
+        loop {
+            match self.peek()? {
+                c if c.is_whitespace() => {
+                    self.next();
+                }
+                ';' => while !matches!(self.next(), None | Some('\n')) {},
+                c => return Some(c),
+            }
+        }
+    }
+
+    fn read(&mut self, arena: &mut types::LispArena) -> Result<types::LispExpRef, ReadError> {
+        match self.skip_blank() {
+            None => Err(self.error(ReadErrorKind::UnexpectedEof)),
+            Some('(') => {
+                self.next();
+                self.read_list(arena)
+            }
+            Some(')') => Err(self.error(ReadErrorKind::UnexpectedChar(')'))),
+            Some('\'') => {
+                self.next();
+                let s_quote = arena.alloc(types::LispAtom::new_symbol("quote").into());
+                let exp = self.read(arena)?;
+                Ok(crate::alloc!(arena, [s_quote, exp]))
+            }
+            Some('"') => {
+                self.next();
+                let s = self.read_string()?;
+                Ok(arena.alloc(s.into()))
+            }
+            Some('#') => {
+                self.next();
+                self.read_hash(arena)
+            }
+            Some(_) => {
+                let (line, column) = (self.line, self.column);
+                let token = self.read_token();
+                if token == "." {
+                    return Err(ReadError {
+                        kind: ReadErrorKind::InvalidDot,
+                        line,
+                        column,
+                    });
+                }
+                Ok(alloc_token(&token, arena))
+            }
+        }
+    }
+
+    /// Read the rest of a `#` form: `#true`, `#false`, `#none`, `#inf`,
+    /// `#-inf`, `#nan`, `#b"bytes"`, `#raw"python code"` or `#f(PART...)`.
+    fn read_hash(&mut self, arena: &mut types::LispArena) -> Result<types::LispExpRef, ReadError> {
+        let (line, column) = (self.line, self.column);
+        let token = self.read_token();
+        let atom: types::LispAtom = match (&*token, self.peek()) {
+            ("true", _) => true.into(),
+            ("false", _) => false.into(),
+            ("none", _) => ().into(),
+            ("inf", _) => f64::INFINITY.into(),
+            ("-inf", _) => f64::NEG_INFINITY.into(),
+            ("nan", _) => f64::NAN.into(),
+            ("b", Some('"')) => {
+                self.next();
+                self.read_bytes()?.into()
+            }
+            ("raw", Some('"')) => {
+                self.next();
+                types::LispAtom::new_raw_text(self.read_string()?)
+            }
+            ("f", Some('(')) => {
+                self.next();
+                self.read_fstring(arena)?.into()
+            }
+            _ => {
+                return Err(ReadError {
+                    kind: ReadErrorKind::InvalidHash(token),
+                    line,
+                    column,
+                })
+            }
+        };
+        Ok(arena.alloc(atom.into()))
+    }
+
+    /// Read the parts of an f-string after `#f(`: strings are literal text,
+    /// `(fmt EXP "SPEC")` an expression with a format spec, `(fmt EXP)` an
+    /// expression that is a string or a `fmt` form, and anything else an
+    /// expression.
+    fn read_fstring(
+        &mut self,
+        arena: &mut types::LispArena,
+    ) -> Result<Vec<types::FStringPart>, ReadError> {
+        let mut parts = Vec::new();
+        loop {
+            match self.skip_blank() {
+                None => return Err(self.error(ReadErrorKind::UnexpectedEof)),
+                Some(')') => {
+                    self.next();
+                    return Ok(parts);
+                }
+                Some(_) => {
+                    let exp = self.read(arena)?;
+                    parts.push(fstring_part(exp));
+                }
+            }
+        }
+    }
+
+    /// Read the rest of a list after its opening paren.
+    fn read_list(&mut self, arena: &mut types::LispArena) -> Result<types::LispExpRef, ReadError> {
+        let mut items = Vec::new();
+        let mut last_cdr = None;
+
+        loop {
+            match self.skip_blank() {
+                None => return Err(self.error(ReadErrorKind::UnexpectedEof)),
+                Some(')') => {
+                    self.next();
+                    break;
+                }
+                Some('.') if self.is_dot() => {
+                    if items.is_empty() || last_cdr.is_some() {
+                        return Err(self.error(ReadErrorKind::InvalidDot));
+                    }
+                    self.next();
+                    last_cdr = Some(self.read(arena)?);
+                    match self.skip_blank() {
+                        Some(')') => (),
+                        None => return Err(self.error(ReadErrorKind::UnexpectedEof)),
+                        Some(_) => return Err(self.error(ReadErrorKind::InvalidDot)),
+                    }
+                }
+                Some(_) => items.push(self.read(arena)?),
+            }
+        }
+
+        let mut cur = match last_cdr {
+            Some(cdr) => cdr,
+            None => crate::alloc!(arena, []),
+        };
+        for item in items.into_iter().rev() {
+            cur = crate::alloc!(arena, [item; cur]);
+        }
+        Ok(cur)
+    }
+
+    /// Whether the upcoming `.` is a lone dot rather than part of a token.
+    fn is_dot(&self) -> bool {
+        let mut chars = self.chars.clone();
+        chars.next();
+        match chars.next() {
+            None => true,
+            Some(c) => is_delimiter(c),
+        }
+    }
+
+    fn read_token(&mut self) -> String {
+        let mut token = String::new();
+        while let Some(c) = self.peek() {
+            if is_delimiter(c) {
+                break;
+            }
+            token.push(c);
+            self.next();
+        }
+        token
+    }
+
+    /// Read the rest of a string literal after its opening quote.
+    fn read_string(&mut self) -> Result<String, ReadError> {
+        let mut s = String::new();
+        loop {
+            match self.next() {
+                None => return Err(self.error(ReadErrorKind::UnterminatedString)),
+                Some('"') => return Ok(s),
+                Some('\\') => s.push(self.read_escape()?),
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    /// Read the rest of a bytes literal after its opening quote.  Like Python,
+    /// only ASCII characters may appear literally.
+    fn read_bytes(&mut self) -> Result<Vec<u8>, ReadError> {
+        let mut b = Vec::new();
+        loop {
+            let c = match self.next() {
+                None => return Err(self.error(ReadErrorKind::UnterminatedString)),
+                Some('"') => return Ok(b),
+                Some('\\') => {
+                    let c = self.read_escape()?;
+                    match u8::try_from(c) {
+                        Ok(c) => c,
+                        Err(_) => return Err(self.error(ReadErrorKind::InvalidByte(c))),
+                    }
+                }
+                Some(c) if c.is_ascii() => c as u8,
+                Some(c) => return Err(self.error(ReadErrorKind::InvalidByte(c))),
+            };
+            b.push(c);
+        }
+    }
+
+    fn read_escape(&mut self) -> Result<char, ReadError> {
+        let c = match self.next() {
+            None => return Err(self.error(ReadErrorKind::UnterminatedString)),
+            Some(c) => c,
+        };
+        match c {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            '0' => Ok('\0'),
+            '\\' | '"' => Ok(c),
+            'x' => {
+                let mut code = String::new();
+                for _ in 0..2 {
+                    match self.next() {
+                        Some(d) if d.is_ascii_hexdigit() => code.push(d),
+                        _ => return Err(self.error(ReadErrorKind::InvalidEscape(c))),
+                    }
+                }
+                Ok(char::from(u8::from_str_radix(&code, 16).unwrap()))
+            }
+            'u' => {
+                if self.next() != Some('{') {
+                    return Err(self.error(ReadErrorKind::InvalidEscape(c)));
+                }
+                let mut code = String::new();
+                loop {
+                    match self.next() {
+                        Some('}') => break,
+                        Some(d) if d.is_ascii_hexdigit() && code.len() < 6 => code.push(d),
+                        _ => return Err(self.error(ReadErrorKind::InvalidEscape(c))),
+                    }
+                }
+                u32::from_str_radix(&code, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| self.error(ReadErrorKind::InvalidEscape(c)))
+            }
+            _ => Err(self.error(ReadErrorKind::InvalidEscape(c))),
+        }
+    }
+}
+
+/// Elements of `exp` if it is a proper list.
+fn proper_list(exp: &types::LispExpRef) -> Option<Vec<types::LispExpRefStrong>> {
+    let mut items = Vec::new();
+    let mut cur = exp.upgrade()?;
+    loop {
+        let next = match &*cur.borrow() {
+            types::LispExp::Atom(types::LispAtom::Symbol(s)) if s == "nil" => return Some(items),
+            types::LispExp::Atom(_) => return None,
+            types::LispExp::Cons { car, cdr } => {
+                items.push(car.upgrade()?);
+                cdr.upgrade()?
+            }
+        };
+        cur = next;
+    }
+}
+
+fn fstring_part(exp: types::LispExpRef) -> types::FStringPart {
+    let exp_ptr = exp.upgrade().unwrap();
+    let exp_ = exp_ptr.borrow();
+    if let types::LispExp::Atom(types::LispAtom::String(s)) = &*exp_ {
+        return types::FStringPart::Text(s.clone());
+    }
+
+    // (fmt EXP "SPEC"), or (fmt EXP) for an expression that would otherwise
+    // read as something else
+    let items = proper_list(&exp).unwrap_or_default();
+    let is_fmt = |x: &types::LispExpRefStrong| matches!(&*x.borrow(), types::LispExp::Atom(types::LispAtom::Symbol(f)) if f == "fmt");
+    let spec = match &items[..] {
+        [head, _] if is_fmt(head) => Some(None),
+        [head, _, spec] if is_fmt(head) => match &*spec.borrow() {
+            types::LispExp::Atom(types::LispAtom::String(spec)) => Some(Some(spec.clone())),
+            _ => None,
+        },
+        _ => None,
+    };
+    if let Some(spec) = spec {
+        return types::FStringPart::Exp {
+            exp: std::rc::Rc::downgrade(&items[1]),
+            spec,
+        };
+    }
+    types::FStringPart::Exp { exp, spec: None }
+}
+
+fn alloc_token(token: &str, arena: &mut types::LispArena) -> types::LispExpRef {
+    if token == "nil" {
+        return arena.alloc_symbol("nil");
+    }
+    if let Some(int) = types::LispAtom::parse_int(token) {
+        return arena.alloc(int.into());
+    }
+
+    let is_number = token.chars().any(|c| c.is_ascii_digit())
+        && token.chars().all(|c| "0123456789+-.eE".contains(c));
+    match token.parse::<f64>() {
+        Ok(float) if is_number => arena.alloc(float.into()),
+        _ => arena.alloc(types::LispAtom::new_symbol(token).into()),
+    }
+}
+
+/// Read exactly one expression from `src`.
+///
+/// # Examples
+///
+/// ```
+/// use pype::{reader, types::*};
+///
+/// let mut arena = LispArena::default();
+/// let e = reader::read("(call print line (kw end \"\"))", &mut arena).unwrap();
+///
+/// assert_eq!(
+///     e.upgrade().unwrap().borrow().to_string(),
+///     "(call print line (kw end \"\"))"
+/// );
+/// ```
+pub fn read(src: &str, arena: &mut types::LispArena) -> Result<types::LispExpRef, ReadError> {
+    let mut reader = Reader::new(src);
+    let exp = reader.read(arena)?;
+    match reader.skip_blank() {
+        None => Ok(exp),
+        Some(c) => Err(reader.error(ReadErrorKind::UnexpectedChar(c))),
+    }
+}
+
+/// Read every expression in `src`.
+pub fn read_all(
+    src: &str,
+    arena: &mut types::LispArena,
+) -> Result<Vec<types::LispExpRef>, ReadError> {
+    let mut reader = Reader::new(src);
+    let mut exps = Vec::new();
+    while reader.skip_blank().is_some() {
+        exps.push(reader.read(arena)?);
+    }
+    Ok(exps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_str(src: &str) -> String {
+        let mut arena = types::LispArena::default();
+        let e = read(src, &mut arena).unwrap();
+        let e_ptr = e.upgrade().unwrap();
+        let x = e_ptr.borrow().to_string();
+        x
+    }
+
+    fn read_err(src: &str) -> (ReadErrorKind, usize, usize) {
+        let mut arena = types::LispArena::default();
+        let err = read(src, &mut arena).unwrap_err();
+        (err.kind, err.line, err.column)
+    }
+
+    #[test]
+    fn test_read_hash() {
+        let mut arena = types::LispArena::default();
+        let atom = |src: &str, arena: &mut types::LispArena| {
+            let e = read(src, arena).unwrap();
+            let e_ptr = e.upgrade().unwrap();
+            let x = match &*e_ptr.borrow() {
+                types::LispExp::Atom(atom) => format!("{:?}", atom),
+                types::LispExp::Cons { .. } => panic!("not an atom"),
+            };
+            x
+        };
+
+        assert_eq!(atom("#true", &mut arena), "Bool(true)");
+        assert_eq!(atom("#false", &mut arena), "Bool(false)");
+        assert_eq!(atom("#none", &mut arena), "None");
+        assert_eq!(atom(r#"#b"a\xff\n""#, &mut arena), "Bytes([97, 255, 10])");
+        assert_eq!(
+            read_str(r#"#f("a" b (fmt c ".2f"))"#),
+            r#"#f("a" b (fmt c ".2f"))"#
+        );
+        assert_eq!(
+            read_str(r#"#f((fmt c) (fmt "d") (fmt (fmt e)) (fmt . f))"#),
+            r#"#f(c (fmt "d") (fmt (fmt e)) (fmt (fmt . f)))"#
+        );
+
+        assert_eq!(
+            read_err("#nil"),
+            (ReadErrorKind::InvalidHash("nil".to_string()), 1, 2)
+        );
+        assert_eq!(
+            read_err("#b(1)"),
+            (ReadErrorKind::InvalidHash("b".to_string()), 1, 2)
+        );
+        assert_eq!(
+            read_err(r#"#b"é""#),
+            (ReadErrorKind::InvalidByte('é'), 1, 5)
+        );
+        assert_eq!(
+            read_err(r#"#b"\u{100}""#),
+            (ReadErrorKind::InvalidByte('\u{100}'), 1, 11)
+        );
+        assert_eq!(read_err("#f(a"), (ReadErrorKind::UnexpectedEof, 1, 5));
+    }
+
+    #[test]
+    fn test_read_atom() {
+        let mut arena = types::LispArena::default();
+        let atom = |src: &str, arena: &mut types::LispArena| {
+            let e = read(src, arena).unwrap();
+            let e_ptr = e.upgrade().unwrap();
+            let x = match &*e_ptr.borrow() {
+                types::LispExp::Atom(atom) => format!("{:?}", atom),
+                types::LispExp::Cons { .. } => panic!("not an atom"),
+            };
+            x
+        };
+
+        assert_eq!(atom("42", &mut arena), "Int(42)");
+        assert_eq!(atom("-7", &mut arena), "Int(-7)");
+        assert_eq!(atom("1.5", &mut arena), "Float(1.5)");
+        assert_eq!(atom("1e3", &mut arena), "Float(1000.0)");
+        assert_eq!(atom("0x1F", &mut arena), "Int(31)");
+        assert_eq!(atom("-0o17", &mut arena), "Int(-15)");
+        assert_eq!(atom("0b_1010", &mut arena), "Int(10)");
+        assert_eq!(atom("1_000", &mut arena), "Int(1000)");
+        assert_eq!(
+            atom("-00123456789012345678901234567890", &mut arena),
+            "BigInt(\"-123456789012345678901234567890\")"
+        );
+        assert_eq!(
+            atom("0x1_0000_0000_0000_0000", &mut arena),
+            "BigInt(\"0x10000000000000000\")"
+        );
+        assert_eq!(atom("1__0", &mut arena), "Symbol(\"1__0\")");
+        assert_eq!(atom("0x", &mut arena), "Symbol(\"0x\")");
+        assert_eq!(atom("#inf", &mut arena), "Float(inf)");
+        assert_eq!(atom("#-inf", &mut arena), "Float(-inf)");
+        assert_eq!(atom("#nan", &mut arena), "Float(NaN)");
+        assert_eq!(atom("-", &mut arena), "Symbol(\"-\")");
+        assert_eq!(atom("inf", &mut arena), "Symbol(\"inf\")");
+        assert_eq!(atom("foo.bar", &mut arena), "Symbol(\"foo.bar\")");
+        assert_eq!(atom("\"a\\\"b\\n\"", &mut arena), "String(\"a\\\"b\\n\")");
+        assert_eq!(atom("\"\\x41\\u{3042}\"", &mut arena), "String(\"Aあ\")");
+    }
+
+    #[test]
+    fn test_read_list() {
+        assert_eq!(read_str("()"), "nil");
+        assert_eq!(read_str("(1 2 3)"), "(1 2 3)");
+        assert_eq!(read_str("  ((1 2)\n 3)  "), "((1 2) 3)");
+        assert_eq!(read_str("(1 . 2)"), "(1 . 2)");
+        assert_eq!(read_str("(1 2 . 3)"), "(1 2 . 3)");
+        assert_eq!(read_str("(1 . (2 3))"), "(1 2 3)");
+        assert_eq!(read_str("'x"), "(quote x)");
+        assert_eq!(read_str("'(a 'b)"), "(quote (a (quote b)))");
+        assert_eq!(
+            read_str("; comment\n(call print ; trailing\n line)"),
+            "(call print line)"
+        );
+    }
+
+    #[test]
+    fn test_read_error() {
+        assert_eq!(read_err("(1 2"), (ReadErrorKind::UnexpectedEof, 1, 5));
+        assert_eq!(
+            read_err("(1\n 2))"),
+            (ReadErrorKind::UnexpectedChar(')'), 2, 4)
+        );
+        assert_eq!(read_err("\"abc"), (ReadErrorKind::UnterminatedString, 1, 5));
+        assert_eq!(
+            read_err("\"\\q\""),
+            (ReadErrorKind::InvalidEscape('q'), 1, 4)
+        );
+        assert_eq!(read_err("(. 1)"), (ReadErrorKind::InvalidDot, 1, 2));
+        assert_eq!(read_err("(1 . 2 3)"), (ReadErrorKind::InvalidDot, 1, 8));
+        assert_eq!(read_err(""), (ReadErrorKind::UnexpectedEof, 1, 1));
+    }
+
+    #[test]
+    fn test_read_all() {
+        let mut arena = types::LispArena::default();
+        let exps = read_all("(a) b ; c\n", &mut arena).unwrap();
+        let strs = exps
+            .iter()
+            .map(|x| x.upgrade().unwrap().borrow().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(strs, vec!["(a)", "b"]);
+    }
+}
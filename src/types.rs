@@ -1,6 +1,7 @@
 mod lisp_arena;
 mod lisp_atom;
 mod lisp_exp;
+mod lisp_pretty;
 
 pub use lisp_arena::*;
 pub use lisp_atom::*;
@@ -1,26 +1,155 @@
+use super::lisp_exp::{LispExp, LispExpRef};
+
 use std::fmt;
 
 #[derive(Debug, PartialEq)]
 pub enum LispAtom {
     Int(i64),
+    /// Integer outside the range of `Int`, as a Python integer literal.
+    BigInt(String),
     Float(f64),
+    Bool(bool),
+    None,
     String(String),
+    Bytes(Vec<u8>),
+    FString(Vec<FStringPart>),
     Symbol(String),
     RawText(String),
 }
 
+/// Piece of an f-string: literal text, or an interpolated expression with an
+/// optional format spec.
+#[derive(Debug)]
+pub enum FStringPart {
+    Text(String),
+    Exp {
+        exp: LispExpRef,
+        spec: Option<String>,
+    },
+}
+
+impl PartialEq for FStringPart {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (FStringPart::Text(t1), FStringPart::Text(t2)) => t1 == t2,
+            (
+                FStringPart::Exp {
+                    exp: exp1,
+                    spec: spec1,
+                },
+                FStringPart::Exp {
+                    exp: exp2,
+                    spec: spec2,
+                },
+            ) => {
+                let exp1_rc = exp1.upgrade().unwrap();
+                let exp2_rc = exp2.upgrade().unwrap();
+                let exp1_ = exp1_rc.borrow();
+                let exp2_ = exp2_rc.borrow();
+
+                *exp1_ == *exp2_ && spec1 == spec2
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for FStringPart {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FStringPart::Text(t) => write_lisp_string(f, t),
+            FStringPart::Exp { exp, spec: None } => {
+                // A string would read back as text, and a `fmt` form as the
+                // part it spells.
+                let exp = exp.upgrade().unwrap();
+                let exp = exp.borrow();
+                let ambiguous = match &*exp {
+                    LispExp::Atom(atom) => matches!(atom, LispAtom::String(_)),
+                    LispExp::Cons { car, .. } => matches!(
+                        &*car.upgrade().unwrap().borrow(),
+                        LispExp::Atom(LispAtom::Symbol(s)) if s == "fmt"
+                    ),
+                };
+                match ambiguous {
+                    true => write!(f, "(fmt {})", exp),
+                    false => write!(f, "{}", exp),
+                }
+            }
+            FStringPart::Exp {
+                exp,
+                spec: Some(spec),
+            } => {
+                write!(f, "(fmt {} ", exp.upgrade().unwrap().borrow())?;
+                write_lisp_string(f, spec)?;
+                write!(f, ")")
+            }
+        }
+    }
+}
+
 impl fmt::Display for LispAtom {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             LispAtom::Int(e) => write!(f, "{}", e),
-            LispAtom::Float(e) => write!(f, "{}", e),
-            LispAtom::String(e) => write!(f, "\"{}\"", e),
+            LispAtom::BigInt(e) => write!(f, "{}", e),
+            LispAtom::Float(e) if e.is_nan() => write!(f, "#nan"),
+            LispAtom::Float(e) if e.is_infinite() && *e > 0.0 => write!(f, "#inf"),
+            LispAtom::Float(e) if e.is_infinite() => write!(f, "#-inf"),
+            LispAtom::Float(e) => write!(f, "{:?}", e),
+            LispAtom::Bool(true) => write!(f, "#true"),
+            LispAtom::Bool(false) => write!(f, "#false"),
+            LispAtom::None => write!(f, "#none"),
+            LispAtom::String(e) => write_lisp_string(f, e),
+            LispAtom::Bytes(e) => write_lisp_bytes(f, e),
+            LispAtom::FString(parts) => {
+                let parts = parts.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+                write!(f, "#f({})", parts.join(" "))
+            }
             LispAtom::Symbol(e) => write!(f, "{}", e),
-            LispAtom::RawText(e) => write!(f, "{}", e),
+            LispAtom::RawText(e) => {
+                write!(f, "#raw")?;
+                write_lisp_string(f, e)
+            }
         }
     }
 }
 
+/// Write `c` as it appears inside a Lisp string literal, with the escapes
+/// the reader accepts.
+fn write_lisp_char(f: &mut fmt::Formatter, c: char) -> fmt::Result {
+    match c {
+        '\\' => write!(f, "\\\\"),
+        '"' => write!(f, "\\\""),
+        '\n' => write!(f, "\\n"),
+        '\t' => write!(f, "\\t"),
+        '\r' => write!(f, "\\r"),
+        '\0' => write!(f, "\\0"),
+        c if c.is_control() && (c as u32) <= 0xff => write!(f, "\\x{:02x}", c as u32),
+        c if c.is_control() => write!(f, "\\u{{{:x}}}", c as u32),
+        c => write!(f, "{}", c),
+    }
+}
+
+fn write_lisp_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        write_lisp_char(f, c)?;
+    }
+    write!(f, "\"")
+}
+
+/// Bytes outside ASCII are always escaped, so they read back as bytes.
+fn write_lisp_bytes(f: &mut fmt::Formatter, b: &[u8]) -> fmt::Result {
+    write!(f, "#b\"")?;
+    for c in b {
+        match c.is_ascii() {
+            true => write_lisp_char(f, *c as char)?,
+            false => write!(f, "\\x{:02x}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
 impl From<i64> for LispAtom {
     fn from(int: i64) -> Self {
         LispAtom::Int(int)
@@ -33,6 +162,36 @@ impl From<f64> for LispAtom {
     }
 }
 
+impl From<bool> for LispAtom {
+    fn from(bool: bool) -> Self {
+        LispAtom::Bool(bool)
+    }
+}
+
+impl From<()> for LispAtom {
+    fn from(_: ()) -> Self {
+        LispAtom::None
+    }
+}
+
+impl From<Vec<u8>> for LispAtom {
+    fn from(bytes: Vec<u8>) -> Self {
+        LispAtom::Bytes(bytes)
+    }
+}
+
+impl From<&[u8]> for LispAtom {
+    fn from(bytes: &[u8]) -> Self {
+        LispAtom::Bytes(bytes.to_vec())
+    }
+}
+
+impl From<Vec<FStringPart>> for LispAtom {
+    fn from(parts: Vec<FStringPart>) -> Self {
+        LispAtom::FString(parts)
+    }
+}
+
 impl From<&str> for LispAtom {
     fn from(string: &str) -> Self {
         LispAtom::String(string.to_string())
@@ -46,6 +205,60 @@ impl From<String> for LispAtom {
 }
 
 impl LispAtom {
+    /// Parse an integer literal as Python writes them: decimal, or
+    /// hexadecimal, octal or binary with a `0x`, `0o` or `0b` prefix, with
+    /// an optional sign and single underscores between digits.  Values that
+    /// do not fit in an `i64` become a `BigInt`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pype::types::*;
+    ///
+    /// assert_eq!(LispAtom::parse_int("-0x1_f"), Some(LispAtom::Int(-31)));
+    /// assert_eq!(
+    ///     LispAtom::parse_int("0XFFFF_FFFF_FFFF_FFFF"),
+    ///     Some(LispAtom::BigInt("0xffffffffffffffff".to_string()))
+    /// );
+    /// assert_eq!(LispAtom::parse_int("0x"), None);
+    /// ```
+    pub fn parse_int(s: &str) -> Option<LispAtom> {
+        let (sign, rest) = match s.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (radix, prefix, digits) = match rest.get(..2).map(|x| x.to_ascii_lowercase()) {
+            Some(p) if p == "0x" => (16, "0x", &rest[2..]),
+            Some(p) if p == "0o" => (8, "0o", &rest[2..]),
+            Some(p) if p == "0b" => (2, "0b", &rest[2..]),
+            _ => (10, "", rest),
+        };
+
+        // Underscores may follow a prefix but must separate digits otherwise.
+        let digits = digits
+            .strip_prefix('_')
+            .filter(|_| radix != 10)
+            .unwrap_or(digits);
+        let valid = !digits.is_empty()
+            && !digits.starts_with('_')
+            && !digits.ends_with('_')
+            && !digits.contains("__")
+            && digits.chars().all(|c| c == '_' || c.is_digit(radix));
+        if !valid {
+            return None;
+        }
+        let digits = digits.replace('_', "").to_ascii_lowercase();
+
+        if let Ok(int) = i64::from_str_radix(&format!("{}{}", sign, digits), radix) {
+            return Some(LispAtom::Int(int));
+        }
+        let digits = match radix {
+            10 => digits.trim_start_matches('0'),
+            _ => &digits,
+        };
+        Some(LispAtom::BigInt(format!("{}{}{}", sign, prefix, digits)))
+    }
+
     pub fn new_symbol<T>(str: T) -> Self
     where
         T: Into<String>,
@@ -0,0 +1,120 @@
+use super::lisp_atom::LispAtom;
+use super::lisp_exp::*;
+
+/// Number of arguments a special form keeps on its first line before its
+/// body, which is indented by two instead of aligned under the first
+/// argument (like `lisp-indent-function` in Emacs).
+fn special_form_args(name: &str) -> Option<usize> {
+    let x = match name {
+        "progn" | "try" | "else" | "finally" => 0,
+        "while" | "if" | "when" | "unless" | "lambda" => 1,
+        "with" | "for" | "def" | "class" | "except" => 2,
+        _ => return None,
+    };
+    Some(x)
+}
+
+/// Column the next character of `buf` is written at.
+fn column(buf: &str) -> usize {
+    match buf.rfind('\n') {
+        Some(i) => buf[i + 1..].chars().count(),
+        None => buf.chars().count(),
+    }
+}
+
+fn newline(buf: &mut String, col: usize) {
+    buf.push('\n');
+    buf.push_str(&" ".repeat(col));
+}
+
+impl LispExp {
+    /// Print the expression so that lines stay within `width` where
+    /// possible.  Lists that do not fit are broken one element per line:
+    /// arguments are aligned under the first one, and the bodies of special
+    /// forms such as `with`, `for` and `lambda` are indented by two.  The
+    /// output reads back to the same tree.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use pype::types::*;
+    ///
+    /// let mut arena = LispArena::default();
+    /// let v1 = arena.alloc(LispExp::new_symbol("for"));
+    /// let v2 = arena.alloc(LispExp::new_symbol("line"));
+    /// let v3 = arena.alloc(LispExp::new_symbol("f"));
+    /// let v4 = arena.alloc(LispExp::new_symbol("call"));
+    /// let v5 = arena.alloc(LispExp::new_symbol("print"));
+    ///
+    /// let e = pype::alloc!(arena, [v1, v2, v3, [v4, v5, v2]]);
+    /// let e = e.upgrade().unwrap();
+    /// assert_eq!(e.borrow().to_pretty_string(80), "(for line f (call print line))");
+    /// assert_eq!(
+    ///     e.borrow().to_pretty_string(20),
+    ///     "(for line f\n  (call print line))"
+    /// );
+    /// assert_eq!(
+    ///     e.borrow().to_pretty_string(10),
+    ///     "(for line f\n  (call print\n        line))"
+    /// );
+    /// ```
+    pub fn to_pretty_string(&self, width: usize) -> String {
+        let mut buf = String::new();
+        self.write_pretty(&mut buf, width);
+        buf
+    }
+
+    /// Write the expression to `buf`, starting at the current column.
+    fn write_pretty(&self, buf: &mut String, width: usize) {
+        let col = column(buf);
+        let flat = self.to_string();
+        if matches!(self, LispExp::Atom(_)) || col + flat.chars().count() <= width {
+            buf.push_str(&flat);
+            return;
+        }
+
+        let mut items = Vec::new();
+        let mut tail = None;
+        for (car, cdr) in self.cons_iter_ptr() {
+            items.push(car);
+            match &*cdr.borrow() {
+                LispExp::Atom(LispAtom::Symbol(s)) if s == "nil" => {}
+                LispExp::Atom(_) => tail = Some(cdr.clone()),
+                LispExp::Cons { .. } => (),
+            }
+        }
+
+        let head = items[0].borrow();
+        let special = match &*head {
+            LispExp::Atom(LispAtom::Symbol(s)) => special_form_args(s),
+            _ => None,
+        };
+
+        buf.push('(');
+        head.write_pretty(buf, width);
+        let rest = &items[1..];
+        let (first_line, body_col) = match (special, &*head) {
+            // Special form: distinguished arguments after the head, then the
+            // body indented by two.
+            (Some(n), _) => (n.min(rest.len()), col + 2),
+            // Call with an atom head: arguments aligned under the first.
+            (None, LispExp::Atom(_)) if !rest.is_empty() => (1, column(buf) + 1),
+            // Otherwise everything is aligned under the head.
+            (None, _) => (0, col + 1),
+        };
+        for item in &rest[..first_line] {
+            buf.push(' ');
+            item.borrow().write_pretty(buf, width);
+        }
+        for item in &rest[first_line..] {
+            newline(buf, body_col);
+            item.borrow().write_pretty(buf, width);
+        }
+        if let Some(tail) = tail {
+            newline(buf, body_col);
+            buf.push_str(". ");
+            tail.borrow().write_pretty(buf, width);
+        }
+        buf.push(')');
+    }
+}
@@ -0,0 +1,208 @@
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+/// Run pype with `args`, feeding it `stdin`.
+fn pype(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pype"))
+        .args(args)
+        .env_remove("PYPE_PYTHON")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn test_bare_in_place_after_long_option() {
+    let args = ["-nle", "print(line)", "f", "--dump-python"];
+    let code = stdout(&pype(&[&["--ofs", "-i"][..], &args].concat(), ""));
+    assert!(code.contains("sep=\"-i\""));
+    assert!(!code.contains("os.replace"));
+
+    let code = stdout(&pype(&[&["--ofs=,", "-i"][..], &args].concat(), ""));
+    assert!(code.contains("sep=\",\""));
+    assert!(code.contains("os.replace"));
+}
+
+#[test]
+fn test_in_place() {
+    let dir = tempfile::tempdir().unwrap();
+    let a = dir.path().join("a.txt");
+    let b = dir.path().join("b.txt");
+    std::fs::write(&a, "1\n2\n").unwrap();
+    std::fs::write(&b, "3\n").unwrap();
+    let files = [a.to_str().unwrap(), b.to_str().unwrap()];
+
+    let args = [&["-r", "-pi.bak", "-e", "line = 'x' + line"][..], &files].concat();
+    assert_eq!(stdout(&pype(&args, "")), "");
+    assert_eq!(std::fs::read_to_string(&a).unwrap(), "x1\nx2\n");
+    assert_eq!(std::fs::read_to_string(&b).unwrap(), "x3\n");
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("a.txt.bak")).unwrap(),
+        "1\n2\n"
+    );
+
+    // A failure leaves the file as it was, and no temporary file behind.
+    let args = [&["-r", "-pi", "-e", "line = 1 / 0"][..], &files[..1]].concat();
+    assert!(!pype(&args, "").status.success());
+    assert_eq!(std::fs::read_to_string(&a).unwrap(), "x1\nx2\n");
+    let mut names = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|x| x.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(names, ["a.txt", "a.txt.bak", "b.txt", "b.txt.bak"]);
+}
+
+#[test]
+fn test_input_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let a = dir.path().join("a.txt");
+    std::fs::write(&a, "1\n2\n").unwrap();
+    let a = a.to_str().unwrap();
+
+    let code = "print(os.path.basename(fileinput.filename()), fileinput.filelineno(), line)";
+    let output = pype(&["-r", "-m", "os", "-nle", code, a, "-"], "3\n");
+    assert_eq!(stdout(&output), "a.txt 1 1\na.txt 2 2\n<stdin> 1 3\n");
+
+    // Without file arguments, fileinput is not used at all.
+    let output = pype(&["-r", "-nle", "print(fileinput.filename())"], "1\n");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("NameError"));
+}
+
+#[test]
+fn test_begin_end() {
+    let args = ["-r", "-nl", "-B", "n = 0", "-e", "n += int(line)"];
+    let output = pype(
+        &[&args[..], &["-E", "print('sum', n)"]].concat(),
+        "1\n2\n3\n",
+    );
+    assert_eq!(stdout(&output), "sum 6\n");
+}
+
+#[test]
+fn test_run() {
+    // python inherits stdin and pype exits with its status.
+    let output = pype(&["-r", "-nle", "print(line.upper())"], "a\nb\n");
+    assert_eq!(stdout(&output), "A\nB\n");
+    let output = pype(&["-r", "-e", "import sys; sys.exit(3)"], "");
+    assert_eq!(output.status.code(), Some(3));
+    let output = pype(&["-r", "-e", "import os; os.kill(os.getpid(), 15)"], "");
+    assert_eq!(output.status.code(), Some(128 + 15));
+
+    // Without -e, stdin is the program.
+    let output = pype(&["-r"], "print(6 * 7)\n");
+    assert_eq!(stdout(&output), "42\n");
+}
+
+#[test]
+fn test_fifo() {
+    // Without -r, the program reads stdin from a FIFO that pype fills once
+    // python has read the program from the pipe.
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pype"))
+        .args(["-nle", "print(line.upper())"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"a\nb\n").unwrap();
+    let python = Command::new("python3")
+        .stdin(child.stdout.take().unwrap())
+        .output()
+        .unwrap();
+    assert!(child.wait().unwrap().success());
+    assert_eq!(String::from_utf8(python.stdout).unwrap(), "A\nB\n");
+}
+
+#[test]
+fn test_python() {
+    // --python and --python-arg imply -r.
+    let output = pype(&["--python", "python3", "-e", "print(__debug__)"], "");
+    assert_eq!(stdout(&output), "True");
+    let output = pype(&["--python-arg", "-O", "-e", "print(__debug__)"], "");
+    assert_eq!(stdout(&output), "False");
+
+    let output = pype(&["--python", "pype-no-such-python", "-e", "pass"], "");
+    assert_eq!(output.status.code(), Some(127));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not found"));
+
+    // $PYPE_PYTHON is used with -r, and --python overrides it.
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_pype"))
+            .args(args)
+            .env("PYPE_PYTHON", "pype-no-such-python")
+            .output()
+            .unwrap()
+    };
+    assert_eq!(run(&["-r", "-e", "pass"]).status.code(), Some(127));
+    let output = run(&["--python", "python3", "-e", "print(1)"]);
+    assert_eq!(stdout(&output), "1");
+}
+
+#[test]
+fn test_output_separators() {
+    let args = ["-r", "-nl", "--ofs=,", "-e", "print(line, len(line))"];
+    assert_eq!(stdout(&pype(&args, "a\nbc\n")), "a,1\nbc,2\n");
+
+    let args = [&args[..], &["-O", "\\x00"]].concat();
+    assert_eq!(stdout(&pype(&args, "a\nbc\n")), "a,1\0bc,2\0");
+}
+
+#[test]
+fn test_lisp() {
+    let output = pype(&["-r", "-nl", "-L", "(call print (call int line))"], "01\n");
+    assert_eq!(stdout(&output), "1\n");
+
+    let output = pype(&["-r", "-L", "(pass)", "-e", "pass"], "");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("cannot be used together"));
+}
+
+#[test]
+fn test_dump() {
+    let output = pype(&["--dump-ir", "-nle", "print(line)", "f"], "");
+    let expect = "\
+(progn (import #raw\"fileinput\") \
+(with (call (attr fileinput input) (kw files (list \"f\"))) f \
+(for line f (progn (assign line (call (attr line rstrip) \"\\n\")) (progn #raw\"print(line)\")))))\n";
+    assert_eq!(stdout(&output), expect);
+
+    let output = pype(&["--dump-steps", "-e", "pass", "f"], "");
+    let steps = stdout(&output)
+        .lines()
+        .filter_map(|x| x.strip_prefix(";; "))
+        .map(String::from)
+        .collect::<Vec<_>>();
+    assert_eq!(steps.first().map(String::as_str), Some("do_e"));
+    assert_eq!(steps.last().map(String::as_str), Some("do_m"));
+
+    let output = pype(&["--dump-python", "-e", "pass", "f"], "");
+    assert!(stdout(&output).ends_with("with fileinput.input(files=[\"f\"]) as f:\n    pass\n"));
+}
+
+#[test]
+fn test_empty_field_separator() {
+    let output = pype(&["-r", "-F", "", "-e", "print(F)"], "a b\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("non-empty separator"));
+}
@@ -0,0 +1,118 @@
+use pype::{reader, types::*};
+
+#[test]
+fn test_round_trip() {
+    let srcs = [
+        "(call print line (kw end \"\"))",
+        "(with (call open \"./temp\") f (for line f (progn (assign line (call (attr line rstrip) \"\\n\")) (call print line))))",
+        "(lambda ((* args) (** kwargs)) (call (attr builtins print) (* args) (** kwargs)))",
+        "(1 2.5 -3 1e100 (a . b) (c d . e))",
+        "(18446744073709551616 -0xffffffffffffffffff 1e-7 -0.0 #inf #-inf)",
+        r#"("C:\\tmp" "say \"hi\"\n" "\t\r\0\x1b\x85" "é")"#,
+        r#"(#true #false #none #b"a\\\"\n\xff" #f("{" x (fmt (call f y) ">5") "\n"))"#,
+        r#"#f((fmt "text") (fmt (fmt x ">3")) (fmt (fmt y)) (fmt z "x"))"#,
+        r#"(progn #raw"print(line)" #raw"x = \"a\\tb\"\n")"#,
+    ];
+
+    for src in srcs {
+        let mut arena = LispArena::default();
+        let e = reader::read(src, &mut arena).unwrap();
+        let printed = e.upgrade().unwrap().borrow().to_string();
+        assert_eq!(printed, src);
+
+        let e2 = reader::read(&printed, &mut arena).unwrap();
+        assert_eq!(
+            *e.upgrade().unwrap().borrow(),
+            *e2.upgrade().unwrap().borrow()
+        );
+    }
+}
+
+#[test]
+fn test_read_matches_alloc() {
+    let mut arena = LispArena::default();
+    let v1 = arena.alloc(LispExp::new_symbol("call"));
+    let v2 = arena.alloc(LispExp::new_symbol("print"));
+    let v3 = arena.alloc(LispExp::new_symbol("line"));
+    let v4 = arena.alloc(LispExp::new_symbol("kw"));
+    let v5 = arena.alloc(LispExp::new_symbol("end"));
+    let v6 = arena.alloc("".into());
+
+    let e1 = pype::alloc!(arena, [v1, v2, v3, [v4, v5, v6]]);
+    let e2 = reader::read("(call print line (kw end \"\"))", &mut arena).unwrap();
+    assert_eq!(
+        *e1.upgrade().unwrap().borrow(),
+        *e2.upgrade().unwrap().borrow()
+    );
+}
+
+#[test]
+fn test_pretty_round_trip() {
+    let src = r#"(progn (import builtins) (def print ((* args) (kw end "\n") (** kwargs)) "doc" (return (call (attr builtins print) (* args) (kw end end) (** kwargs)))) (with (call open "a b") f (for (i line) (call enumerate f) (try (call print #f("x" (fmt i ">3")) #b"\xff") (except ValueError e (pass)) (finally (a . b))))))"#;
+
+    let mut arena = LispArena::default();
+    let e = reader::read(src, &mut arena).unwrap();
+    for width in [1, 20, 40, 80, 400] {
+        let pretty = e.upgrade().unwrap().borrow().to_pretty_string(width);
+        let e2 = reader::read(&pretty, &mut arena).unwrap();
+        assert_eq!(
+            *e.upgrade().unwrap().borrow(),
+            *e2.upgrade().unwrap().borrow()
+        );
+        if width == 400 {
+            assert_eq!(pretty, src);
+        }
+    }
+
+    let pretty = e.upgrade().unwrap().borrow().to_pretty_string(60);
+    let expect = r#"(progn
+  (import builtins)
+  (def print ((* args) (kw end "\n") (** kwargs))
+    "doc"
+    (return (call (attr builtins print)
+                  (* args)
+                  (kw end end)
+                  (** kwargs))))
+  (with (call open "a b") f
+    (for (i line) (call enumerate f)
+      (try
+        (call print #f("x" (fmt i ">3")) #b"\xff")
+        (except ValueError e (pass))
+        (finally (a . b))))))"#;
+    assert_eq!(pretty, expect);
+    assert!(pretty.lines().all(|x| x.chars().count() <= 60));
+}
+
+#[test]
+fn test_pretty_round_trip_pipeline() {
+    // File arguments keep the program free of per-run FIFO paths.
+    let dump_ir = |extra: &[&str]| {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_pype"))
+            .args(["-n", "-a", "-F", ",", "-m", "re"])
+            .args([
+                "-B",
+                "n = 0",
+                "-e",
+                "n += len(F)",
+                "-E",
+                "print(\"n =\", n)",
+            ])
+            .args(["--dump-ir", "input.txt"])
+            .args(extra)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let flat = dump_ir(&[]);
+    let pretty = dump_ir(&["--pretty", "--width", "40"]);
+    assert!(pretty.contains("#raw\"print(\\\"n =\\\", n)\""));
+
+    let mut arena = LispArena::default();
+    let e = reader::read(&flat, &mut arena).unwrap();
+    let e2 = reader::read(&pretty, &mut arena).unwrap();
+    assert_eq!(
+        *e.upgrade().unwrap().borrow(),
+        *e2.upgrade().unwrap().borrow()
+    );
+}